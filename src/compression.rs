@@ -0,0 +1,184 @@
+use crate::tree_store::MAX_VALUE_LENGTH;
+use crate::types::{RedbValue, TypeName};
+use crate::{Result, StorageError};
+use std::fmt::Debug;
+#[cfg(feature = "zstd")]
+use std::io::Read;
+use std::marker::PhantomData;
+
+const STORED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+/// A compression algorithm which can be used with [`Compressed`]
+pub trait Codec: Debug + 'static {
+    /// Compresses `data`, returning `None` if it can't be compressed, in which case it's stored
+    /// uncompressed. The result may be larger than the input
+    fn compress(data: &[u8]) -> Option<Vec<u8>>;
+
+    /// Decompresses data produced by [`Self::compress`], returning `None` if it is invalid
+    ///
+    /// `uncompressed_len` is the length of the data that was passed to [`Self::compress`]. It is
+    /// read from the stored value, so may be corrupt, and shouldn't be allocated up front unless
+    /// it's consistent with the length of `data`
+    fn decompress(data: &[u8], uncompressed_len: usize) -> Option<Vec<u8>>;
+
+    /// Globally unique name of the codec, which is recorded in the [`TypeName`] of the value
+    fn name() -> &'static str;
+}
+
+/// Stores values of type `T` compressed with the codec `C`
+///
+/// Values are prefixed with a flag byte. If compression doesn't make a value smaller, it is
+/// stored as is; otherwise the flag is followed by its uncompressed length, as a little-endian
+/// `u32`, and the compressed bytes.
+///
+/// `T` must deserialize to an owned value of its own type, such as `String` or `Vec<u8>`, since
+/// the decompressed bytes only live as long as the call to `from_bytes()`
+#[derive(Debug)]
+pub struct Compressed<C, T>(PhantomData<(C, T)>);
+
+impl<C: Codec, T> Compressed<C, T> {
+    fn decode(data: &[u8]) -> Result<T>
+    where
+        T: for<'a> RedbValue<SelfType<'a> = T> + 'static,
+    {
+        let invalid = || {
+            StorageError::Corrupted(format!(
+                "Invalid data for {}: {:?}",
+                Self::type_name().name(),
+                &data[..data.len().min(32)]
+            ))
+        };
+        match data.split_first() {
+            Some((&STORED, stored)) => T::try_from_bytes(stored),
+            Some((&COMPRESSED, rest)) if rest.len() >= 4 => {
+                let (len, compressed) = rest.split_at(4);
+                let len: usize = u32::from_le_bytes(len.try_into().unwrap())
+                    .try_into()
+                    .unwrap();
+                // The length is used to allocate the output, so check it before decompressing
+                if len > MAX_VALUE_LENGTH {
+                    return Err(invalid());
+                }
+                let uncompressed = C::decompress(compressed, len)
+                    .filter(|x| x.len() == len)
+                    .ok_or_else(invalid)?;
+                T::try_from_bytes(&uncompressed)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl<C: Codec, T> RedbValue for Compressed<C, T>
+where
+    T: for<'a> RedbValue<SelfType<'a> = T> + 'static,
+{
+    type SelfType<'a> = T
+    where
+        Self: 'a;
+    type AsBytes<'a> = Vec<u8>
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> T
+    where
+        Self: 'a,
+    {
+        Self::decode(data).unwrap()
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<T>
+    where
+        Self: 'a,
+    {
+        Self::decode(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a T) -> Vec<u8>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        let bytes = T::as_bytes(value);
+        let bytes = bytes.as_ref();
+        let compressed = C::compress(bytes).filter(|x| x.len() + 4 < bytes.len());
+        if let Some(compressed) = compressed {
+            let mut result = Vec::with_capacity(compressed.len() + 5);
+            result.push(COMPRESSED);
+            result.extend_from_slice(&u32::try_from(bytes.len()).unwrap().to_le_bytes());
+            result.extend_from_slice(&compressed);
+            result
+        } else {
+            let mut result = Vec::with_capacity(bytes.len() + 1);
+            result.push(STORED);
+            result.extend_from_slice(bytes);
+            result
+        }
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal(&format!(
+            "Compressed<{},{}>",
+            C::name(),
+            T::type_name().name()
+        ))
+    }
+}
+
+/// [LZ4](https://docs.rs/lz4_flex) compression, which is very fast but has a lower compression
+/// ratio than Zstandard
+#[cfg(feature = "lz4")]
+#[derive(Debug)]
+pub struct Lz4;
+
+#[cfg(feature = "lz4")]
+impl Codec for Lz4 {
+    fn compress(data: &[u8]) -> Option<Vec<u8>> {
+        Some(lz4_flex::compress(data))
+    }
+
+    fn decompress(data: &[u8], uncompressed_len: usize) -> Option<Vec<u8>> {
+        // Each byte of an LZ4 block decompresses to at most 255 bytes
+        if uncompressed_len > data.len().saturating_mul(255) {
+            return None;
+        }
+        lz4_flex::decompress(data, uncompressed_len).ok()
+    }
+
+    fn name() -> &'static str {
+        "lz4"
+    }
+}
+
+/// [Zstandard](https://docs.rs/zstd) compression, at the default compression level
+#[cfg(feature = "zstd")]
+#[derive(Debug)]
+pub struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Codec for Zstd {
+    fn compress(data: &[u8]) -> Option<Vec<u8>> {
+        zstd::bulk::compress(data, 0).ok()
+    }
+
+    fn decompress(data: &[u8], uncompressed_len: usize) -> Option<Vec<u8>> {
+        // Zstandard's compression ratio is unbounded, so the output is grown as it's decompressed,
+        // rather than allocated up front
+        let mut result = vec![];
+        zstd::stream::read::Decoder::with_buffer(data)
+            .ok()?
+            .take(uncompressed_len as u64 + 1)
+            .read_to_end(&mut result)
+            .ok()?;
+        Some(result)
+    }
+
+    fn name() -> &'static str {
+        "zstd"
+    }
+}
@@ -1097,6 +1097,54 @@ mod test {
         assert!(final_file_size < file_size);
     }
 
+    #[test]
+    fn page_sizes_same_contents() {
+        let table_def: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+        let mut results = vec![];
+        for page_size in [4 * 1024, 16 * 1024] {
+            let tmpfile = crate::create_tempfile();
+            let mut db = Database::builder()
+                .set_page_size(page_size)
+                .create(tmpfile.path())
+                .unwrap();
+
+            let tx = db.begin_write().unwrap();
+            {
+                let mut t = tx.open_table(table_def).unwrap();
+                for i in 0..5000u64 {
+                    t.insert(&i, vec![i.to_le_bytes()[0]; (i % 300) as usize].as_slice())
+                        .unwrap();
+                }
+                // Shrink some leaves enough to be merged with their neighbours
+                for i in (0..5000u64).filter(|i| i % 7 != 0 && (1000..3000).contains(i)) {
+                    t.remove(&i).unwrap();
+                }
+            }
+            let stats = tx.stats().unwrap();
+            assert_eq!(stats.page_size(), page_size);
+            tx.commit().unwrap();
+            db.check_integrity().unwrap();
+
+            let tx = db.begin_read().unwrap();
+            let t = tx.open_table(table_def).unwrap();
+            let entries: Vec<(u64, Vec<u8>)> = t
+                .iter()
+                .unwrap()
+                .map(|x| {
+                    let (k, v) = x.unwrap();
+                    (k.value(), v.value().to_vec())
+                })
+                .collect();
+            results.push((stats.leaf_pages(), entries));
+        }
+
+        let (small_leaves, small_entries) = &results[0];
+        let (large_leaves, large_entries) = &results[1];
+        assert_eq!(small_entries.len(), 3000 + 2000 / 7 + 1);
+        assert_eq!(small_entries, large_entries);
+        assert!(large_leaves < small_leaves);
+    }
+
     #[test]
     fn create_new_db_in_empty_file() {
         let tmpfile = crate::create_tempfile();
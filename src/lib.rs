@@ -9,6 +9,7 @@
 // TODO remove this once wasi no longer requires nightly
 #![cfg_attr(target_os = "wasi", feature(wasi_ext))]
 
+pub use compression::{Codec, Compressed};
 pub use db::{
     Builder, Database, MultimapTableDefinition, MultimapTableHandle, TableDefinition, TableHandle,
     UntypedMultimapTableHandle, UntypedTableHandle,
@@ -20,22 +21,36 @@ pub use error::{
 pub use multimap_table::{
     MultimapRange, MultimapTable, MultimapValue, ReadOnlyMultimapTable, ReadableMultimapTable,
 };
-pub use table::{Drain, DrainFilter, Range, ReadOnlyTable, ReadableTable, Table};
+pub use table::{
+    Drain, DrainFilter, Keys, MapEntries, Range, RangeMut, ReadOnlyTable, ReadableTable, Table,
+    TableStats, Values,
+};
 pub use transactions::{DatabaseStats, Durability, ReadTransaction, WriteTransaction};
-pub use tree_store::{AccessGuard, AccessGuardMut, Savepoint};
+pub use tree_store::{AccessGuard, AccessGuardMut, Savepoint, MAX_VALUE_LENGTH};
 pub use types::{RedbKey, RedbValue, TypeName};
 
 type Result<T = (), E = StorageError> = std::result::Result<T, E>;
 
 #[cfg(feature = "python")]
 pub use crate::python::redb;
+#[cfg(feature = "lz4")]
+pub use compression::Lz4;
+#[cfg(feature = "zstd")]
+pub use compression::Zstd;
+#[cfg(feature = "serde")]
+pub use serde_types::{Bincode, BincodeValue};
+#[cfg(feature = "serde")]
+pub use tree_store::TreeNode;
 
+mod compression;
 mod db;
 mod error;
 mod multimap_table;
 #[cfg(feature = "python")]
 mod python;
 mod sealed;
+#[cfg(feature = "serde")]
+mod serde_types;
 mod table;
 mod transaction_tracker;
 mod transactions;
@@ -0,0 +1,71 @@
+use crate::types::{invalid_data, RedbValue, TypeName};
+use crate::Result;
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+/// A type which can be stored with [`Bincode`]
+pub trait BincodeValue: Serialize + DeserializeOwned + Debug {
+    /// Name of the type, which is recorded for each table that stores it
+    ///
+    /// It must be unique among the types stored with [`Bincode`], and must not change once a table
+    /// has been created, or the table will fail to open with a type mismatch
+    const NAME: &'static str;
+}
+
+// The encoding used by bincode::serialize(), but reading no more than data when deserializing, so
+// that a corrupt length prefix in it can't cause a huge allocation
+fn deserialize<T: DeserializeOwned>(data: &[u8]) -> bincode::Result<T> {
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(data.len() as u64)
+        .deserialize(data)
+}
+
+/// Stores values of type `T` by serializing them with [bincode](https://docs.rs/bincode)
+///
+/// The type name recorded for the table is derived from [`BincodeValue::NAME`]
+#[derive(Debug)]
+pub struct Bincode<T>(PhantomData<T>);
+
+impl<T: BincodeValue> RedbValue for Bincode<T> {
+    type SelfType<'a> = T
+    where
+        Self: 'a;
+    type AsBytes<'a> = Vec<u8>
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> T
+    where
+        Self: 'a,
+    {
+        deserialize(data).unwrap()
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<T>
+    where
+        Self: 'a,
+    {
+        deserialize(data).map_err(|_| invalid_data(Self::type_name().name(), data))
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a T) -> Vec<u8>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        bincode::serialize(value).unwrap()
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal(&format!("Bincode<{}>", T::NAME))
+    }
+}
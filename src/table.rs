@@ -1,12 +1,13 @@
 use crate::sealed::Sealed;
 use crate::tree_store::{
     AccessGuardMut, Btree, BtreeDrain, BtreeDrainFilter, BtreeMut, BtreeRangeIter, Checksum,
-    PageHint, PageNumber, TransactionalMemory, MAX_VALUE_LENGTH,
+    PageHint, PageNumber, RangeMutPosition, TransactionalMemory, MAX_VALUE_LENGTH,
 };
 use crate::types::{RedbKey, RedbValue, RedbValueMutInPlace};
 use crate::Result;
 use crate::{AccessGuard, StorageError, WriteTransaction};
 use std::borrow::Borrow;
+use std::io::{ErrorKind, Read, Write};
 use std::ops::RangeBounds;
 use std::sync::{Arc, Mutex};
 
@@ -85,6 +86,67 @@ impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> Table<'db, 'txn, K
         self.tree.drain(&range).map(Drain::new)
     }
 
+    /// Removes all entries from the table
+    ///
+    /// This frees all of the table's pages directly, and is much faster than removing the
+    /// entries one at a time
+    pub fn clear(&mut self) -> Result {
+        self.tree.clear()
+    }
+
+    /// Rewrites the table so that its pages are densely packed
+    ///
+    /// Removing entries leaves pages partially empty. This rebuilds the table from its entries,
+    /// which are unchanged, and frees the old pages. If an error occurs, the table is left
+    /// unchanged. Use [`crate::Database::compact`] afterwards to also shrink the database file
+    pub fn compact(&mut self) -> Result {
+        self.tree.compact()
+    }
+
+    /// Retains only the entries for which `predicate` returns `true`
+    ///
+    /// `predicate` is called for every entry first, and then the table is rebuilt from the
+    /// retained entries in a single pass, and the old pages are freed. This is faster than removing
+    /// the other entries individually when many are removed. If an error occurs, the table is left
+    /// unchanged
+    pub fn retain<F: for<'f> FnMut(K::SelfType<'f>, V::SelfType<'f>) -> bool>(
+        &mut self,
+        predicate: F,
+    ) -> Result {
+        self.tree.retain(predicate)
+    }
+
+    /// Removes all entries in the specified range, and returns the number of entries removed
+    ///
+    /// Unlike [`Self::drain`], the removed entries are not returned. Pages which lie entirely within
+    /// the range are freed without being rewritten
+    pub fn remove_range<'a, KR>(&mut self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.tree.remove_range(&range)
+    }
+
+    /// Removes the entries in the specified range in ascending key order, and returns the number
+    /// of entries removed
+    ///
+    /// The range is removed in chunks of consecutive entries, so memory use doesn't grow with its
+    /// size. `progress` is called with the number of entries removed so far after each chunk. If
+    /// it returns `false`, no further entries are removed, leaving only the entries at the end of
+    /// the range
+    pub fn remove_range_with_progress<'a, KR>(
+        &mut self,
+        range: impl RangeBounds<KR> + 'a,
+        progress: impl FnMut(u64) -> bool,
+    ) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.tree.remove_range_with_progress(&range, progress)
+    }
+
     /// Applies `predicate` to all key-value pairs in the specified range. All entries for which
     /// `predicate` evaluates to `true` are removed and returned in an iterator
     pub fn drain_filter<'a, KR, F: for<'f> Fn(K::SelfType<'f>, V::SelfType<'f>) -> bool>(
@@ -104,6 +166,10 @@ impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> Table<'db, 'txn, K
     /// Insert mapping of the given key to the given value
     ///
     /// Returns the old value, if the key was present in the table
+    ///
+    /// Keys and values larger than a page are stored in their own pages. Keys and values may each
+    /// be up to [`MAX_VALUE_LENGTH`](crate::MAX_VALUE_LENGTH) bytes, and
+    /// [`StorageError::ValueTooLarge`] is returned for anything larger
     pub fn insert<'k, 'v>(
         &mut self,
         key: impl Borrow<K::SelfType<'k>>,
@@ -120,6 +186,142 @@ impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> Table<'db, 'txn, K
         self.tree.insert(key.borrow(), value.borrow())
     }
 
+    /// Insert mapping of the given key to the given value, which are already encoded
+    ///
+    /// This avoids decoding and re-encoding entries when copying them between tables of the same
+    /// type, for example from [`Range::map_entries`]. The bytes must be valid encodings of `K` and
+    /// `V`, as returned by [`RedbValue::as_bytes`]
+    ///
+    /// Returns the old value, if the key was present in the table
+    ///
+    /// Panics if the length of `key` or `value` doesn't match the fixed width of its type
+    pub fn insert_raw(&mut self, key: &[u8], value: &[u8]) -> Result<Option<AccessGuard<V>>> {
+        if let Some(width) = K::fixed_width() {
+            assert_eq!(
+                key.len(),
+                width,
+                "insert_raw() requires keys of {width} bytes"
+            );
+        }
+        if let Some(width) = V::fixed_width() {
+            assert_eq!(
+                value.len(),
+                width,
+                "insert_raw() requires values of {width} bytes"
+            );
+        }
+        if value.len() > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(value.len()));
+        }
+        if key.len() > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(key.len()));
+        }
+        self.tree.insert_raw(key, value)
+    }
+
+    /// Insert mapping of the given key to the given value, only if the key is not already present
+    ///
+    /// Returns `true` if the value was inserted, or `false` if the key was present, in which case
+    /// its value is unchanged and no pages are written. The key is looked up in the same traversal
+    /// of the btree that inserts it
+    pub fn insert_if_absent<'k, 'v>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'k>>,
+        value: impl Borrow<V::SelfType<'v>>,
+    ) -> Result<bool> {
+        let value_len = V::as_bytes(value.borrow()).as_ref().len();
+        if value_len > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(value_len));
+        }
+        let key_len = K::as_bytes(key.borrow()).as_ref().len();
+        if key_len > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(key_len));
+        }
+        self.tree.insert_if_absent(key.borrow(), value.borrow())
+    }
+
+    /// Returns the value for the given key, first inserting the value returned by `f` if the key is
+    /// not present
+    ///
+    /// `f` is only called if the key is not present. The key is looked up and the value inserted
+    /// in a single traversal of the btree
+    pub fn get_or_insert_with<'k, 'v, VR>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'k>>,
+        f: impl FnOnce() -> VR,
+    ) -> Result<AccessGuard<V>>
+    where
+        VR: Borrow<V::SelfType<'v>>,
+    {
+        self.tree.get_or_insert_with(key.borrow(), f)
+    }
+
+    /// Insert all of the given key-value pairs
+    ///
+    /// This is equivalent to calling [`Self::insert`] for each pair, except that the old values
+    /// are not returned and no guard is created for the inserted values, which makes it cheaper
+    /// for large batches
+    pub fn insert_many<'k, 'v, KR, VR>(
+        &mut self,
+        entries: impl IntoIterator<Item = (KR, VR)>,
+    ) -> Result
+    where
+        KR: Borrow<K::SelfType<'k>>,
+        VR: Borrow<V::SelfType<'v>>,
+    {
+        self.tree.insert_many(entries)
+    }
+
+    /// Insert all of the given key-value pairs, which must be in strictly increasing order by key
+    ///
+    /// Pairs with keys greater than every key already in the table are appended by rewriting
+    /// only the pages along the right edge of the table, which is much faster than inserting them
+    /// individually. Pairs which interleave with existing keys are inserted individually
+    ///
+    /// Panics if the keys are not in strictly increasing order
+    pub fn extend_sorted<'k, 'v, KR, VR>(
+        &mut self,
+        entries: impl IntoIterator<Item = (KR, VR)>,
+    ) -> Result
+    where
+        KR: Borrow<K::SelfType<'k>>,
+        VR: Borrow<V::SelfType<'v>>,
+    {
+        self.tree.extend_sorted(entries)
+    }
+
+    /// Inserts all of the key-value pairs written by [`ReadableTable::export`] from `reader`
+    ///
+    /// The pairs are validated and built into a new tree as they're read, so the input is never
+    /// held in memory, and the table is only modified once all of them have been read. That tree
+    /// becomes the table if it's empty, and is otherwise merged into it
+    ///
+    /// Returns [`StorageError::Corrupted`] if the input is truncated, if a key or value can't be
+    /// decoded, or if the keys are not in strictly increasing order, and [`StorageError::Io`] if
+    /// reading fails
+    pub fn import(&mut self, mut reader: impl Read) -> Result {
+        let mut buffer = [0u8; 8];
+        read_import_bytes(&mut reader, &mut buffer)?;
+        let count = u64::from_le_bytes(buffer);
+        let mut previous_key: Option<Vec<u8>> = None;
+        let entries = (0..count).map(|_| {
+            let key = read_length_prefixed(&mut reader)?;
+            let value = read_length_prefixed(&mut reader)?;
+            check_encoded::<K>(&key)?;
+            check_encoded::<V>(&value)?;
+            if let Some(ref previous_key) = previous_key {
+                if !K::compare(previous_key, &key).is_lt() {
+                    return Err(StorageError::Corrupted(
+                        "Imported keys are not in strictly increasing order".to_string(),
+                    ));
+                }
+            }
+            previous_key = Some(key.clone());
+            Ok((key, value))
+        });
+        self.tree.extend_sorted_encoded(entries)
+    }
+
     /// Removes the given key
     ///
     /// Returns the old value, if the key was present in the table
@@ -154,6 +356,56 @@ impl<'db, 'txn, K: RedbKey + 'static, V: RedbValueMutInPlace + 'static> Table<'d
         }
         self.tree.insert_reserve(key.borrow(), value_length)
     }
+
+    /// Appends `extra` to the end of the value of the given key. If the key is not present, it is
+    /// inserted with `extra` as its value
+    pub fn append_value<'a>(&mut self, key: impl Borrow<K::SelfType<'a>>, extra: &[u8]) -> Result
+    where
+        K: 'a,
+    {
+        let key_len = K::as_bytes(key.borrow()).as_ref().len();
+        if key_len > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(key_len));
+        }
+        self.tree.append_value(key.borrow(), extra)
+    }
+
+    /// Returns a mutable reference to the value of the given key, if it is present
+    ///
+    /// The length of the value is fixed. Use [`Self::insert_reserve`] to change it
+    ///
+    /// If the value's page has already been written by this transaction, the value is modified
+    /// where it is. Otherwise it's copied, along with the pages above it, the same as if it were
+    /// inserted again, so that the last commit is left unchanged
+    pub fn get_mut<'a>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'a>>,
+    ) -> Result<Option<AccessGuardMut<V>>>
+    where
+        K: 'a,
+    {
+        self.tree.get_mut(key.borrow())
+    }
+
+    /// Returns a [`RangeMut`], which gives mutable access to the value of each entry in the given
+    /// range in turn
+    ///
+    /// The first entry of each leaf in the range is looked up from the root of the btree, the same
+    /// as [`Self::get_mut`], and the following entries of that leaf are then modified in place. The
+    /// length of the values is fixed
+    pub fn range_mut<'a, KR>(
+        &mut self,
+        range: impl RangeBounds<KR> + 'a,
+    ) -> Result<RangeMut<'_, 'txn, K, V>>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        Ok(RangeMut {
+            position: RangeMutPosition::new::<K, _, KR>(&range),
+            tree: &mut self.tree,
+        })
+    }
 }
 
 impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> ReadableTable<K, V>
@@ -178,8 +430,51 @@ impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> ReadableTable<K, V
         self.tree.len()
     }
 
+    fn count_range<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.tree.count_range(&range)
+    }
+
+    fn select_nth(&self, n: u64) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        self.tree.select_nth(n)
+    }
+
+    fn split_keys(&self, n: u64) -> Result<Vec<AccessGuard<K>>> {
+        self.tree.split_keys(n)
+    }
+
+    fn separator_keys(&self, levels: usize) -> Result<Vec<AccessGuard<K>>> {
+        self.tree.separator_keys(levels)
+    }
+
     fn is_empty(&self) -> Result<bool> {
-        self.len().map(|x| x == 0)
+        // Removing the last entry frees the root page, so only an empty table has no root
+        Ok(self.tree.get_root().is_none())
+    }
+
+    fn verify_structure(&self) -> Result {
+        self.tree.verify_structure()
+    }
+
+    #[cfg(feature = "serde")]
+    fn tree_structure(&self) -> Result<Option<crate::TreeNode>> {
+        self.tree.tree_structure()
+    }
+
+    fn stats(&self) -> Result<TableStats> {
+        let stats = self.tree.stats()?;
+        Ok(TableStats {
+            tree_height: stats.tree_height,
+            pages_per_level: stats.pages_per_level,
+            leaf_pages: stats.leaf_pages,
+            branch_pages: stats.branch_pages,
+            stored_leaf_bytes: stats.stored_leaf_bytes,
+            metadata_bytes: stats.metadata_bytes,
+            fragmented_bytes: stats.fragmented_bytes,
+        })
     }
 }
 
@@ -192,6 +487,58 @@ impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> Drop for Table<'db
     }
 }
 
+/// Informational storage stats about a table
+#[derive(Debug)]
+pub struct TableStats {
+    pub(crate) tree_height: u32,
+    pub(crate) pages_per_level: Vec<u64>,
+    pub(crate) leaf_pages: u64,
+    pub(crate) branch_pages: u64,
+    pub(crate) stored_leaf_bytes: u64,
+    pub(crate) metadata_bytes: u64,
+    pub(crate) fragmented_bytes: u64,
+}
+
+impl TableStats {
+    /// Maximum traversal distance to reach the deepest (key, value) pair in the table
+    pub fn tree_height(&self) -> u32 {
+        self.tree_height
+    }
+
+    /// Number of pages on each level of the btree, starting with the root.
+    /// The last level contains the leaf pages
+    pub fn pages_per_level(&self) -> &[u64] {
+        &self.pages_per_level
+    }
+
+    /// Number of leaf pages that store the table's data
+    pub fn leaf_pages(&self) -> u64 {
+        self.leaf_pages
+    }
+
+    /// Number of branch pages in the table's btree
+    pub fn branch_pages(&self) -> u64 {
+        self.branch_pages
+    }
+
+    /// Number of bytes consumed by keys and values that have been inserted.
+    /// Does not include indexing overhead
+    pub fn stored_bytes(&self) -> u64 {
+        self.stored_leaf_bytes
+    }
+
+    /// Number of bytes consumed by keys in internal branch pages, plus other metadata
+    pub fn metadata_bytes(&self) -> u64 {
+        self.metadata_bytes
+    }
+
+    /// Number of bytes in the table's pages which are unused. A large value relative to
+    /// [`Self::stored_bytes`] indicates that compacting the database may reclaim space
+    pub fn fragmented_bytes(&self) -> u64 {
+        self.fragmented_bytes
+    }
+}
+
 pub trait ReadableTable<K: RedbKey + 'static, V: RedbValue + 'static>: Sealed {
     /// Returns the value corresponding to the given key
     fn get<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<Option<AccessGuard<V>>>
@@ -238,13 +585,139 @@ pub trait ReadableTable<K: RedbKey + 'static, V: RedbValue + 'static>: Sealed {
     /// Returns the number of entries in the table
     fn len(&self) -> Result<u64>;
 
+    /// Returns the number of entries in the specified range
+    ///
+    /// This is faster than counting the entries returned by [`Self::range`], since entries are
+    /// counted a page at a time rather than individually
+    fn count_range<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a;
+
+    /// Returns the entry at position `n` in key order, or `None` if the table has `n` or fewer
+    /// entries
+    ///
+    /// Entries before position `n` are counted a page at a time, rather than individually, but
+    /// every leaf page before it is still read, so this takes time linear in `n`
+    fn select_nth(&self, n: u64) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>>;
+
+    /// Returns up to `n - 1` keys which divide the table into `n` ranges, each holding roughly the
+    /// same number of entries. This is useful for scanning a table in parallel
+    ///
+    /// The returned keys are in increasing order, and the ranges are `..keys[0]`,
+    /// `keys[0]..keys[1]`, and so on, up to `keys[n - 2]..`. Fewer keys are returned if the table
+    /// has fewer than `n` entries
+    ///
+    /// The entries of every leaf page are counted in a single pass, and then only the leaf pages
+    /// which hold the returned keys are read again
+    fn split_keys(&self, n: u64) -> Result<Vec<AccessGuard<K>>>;
+
+    /// Returns the separator keys of the branch pages on the first `levels` levels of the table's
+    /// btree, starting from the root, in increasing order
+    ///
+    /// Only branch pages are read, along with the leftmost path to a leaf, which gives the height of
+    /// the btree. This is much cheaper than scanning the table, and gives a sample of its keyspace,
+    /// for example to build a histogram for query planning. The keys aren't evenly spaced by
+    /// entries, since each is an upper bound on the keys to its left within its branch, and
+    /// subtrees can hold different numbers of entries; [`Self::split_keys`] gives evenly sized
+    /// ranges, at a higher cost. A separator may be a key which has since been removed
+    fn separator_keys(&self, levels: usize) -> Result<Vec<AccessGuard<K>>>;
+
+    /// Returns the number of entries with keys less than `key`
+    ///
+    /// This is the position that `key` has, or would have if it were inserted, in key order. It's
+    /// computed with [`Self::count_range`], which reads every leaf page before `key`, so this takes
+    /// time linear in the rank
+    fn rank<'a>(&self, key: impl Borrow<K::SelfType<'a>> + 'a) -> Result<u64>
+    where
+        K: 'a,
+    {
+        self.count_range(..key)
+    }
+
     /// Returns `true` if the table is empty
     fn is_empty(&self) -> Result<bool>;
 
+    /// Retrieves information about storage usage for the table
+    fn stats(&self) -> Result<TableStats>;
+
+    /// Checks that the keys in the table are correctly ordered, and that its btree is balanced
+    ///
+    /// Returns [`StorageError::Corrupted`] describing the first problem found
+    fn verify_structure(&self) -> Result;
+
+    /// Returns the nodes of the table's btree, starting from its root, or `None` if it's empty
+    ///
+    /// This is intended for inspecting or visualizing the table, e.g. after serializing it to JSON
+    #[cfg(feature = "serde")]
+    fn tree_structure(&self) -> Result<Option<crate::TreeNode>>;
+
+    /// Returns the first key-value pair in the table, if any
+    fn first(&self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        self.iter()?.next().transpose()
+    }
+
+    /// Returns the last key-value pair in the table, if any
+    fn last(&self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        self.iter()?.next_back().transpose()
+    }
+
+    /// Returns the first key-value pair in the specified range, if any
+    ///
+    /// Only the path to the start of the range is read, so this is a cheap way to find the
+    /// minimum of a range
+    fn first_in_range<'a, KR>(
+        &self,
+        range: impl RangeBounds<KR> + 'a,
+    ) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.range(range)?.next().transpose()
+    }
+
+    /// Returns the last key-value pair in the specified range, if any
+    ///
+    /// Only the path to the end of the range is read, so this is a cheap way to find the
+    /// maximum of a range
+    fn last_in_range<'a, KR>(
+        &self,
+        range: impl RangeBounds<KR> + 'a,
+    ) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.range(range)?.next_back().transpose()
+    }
+
     /// Returns a double-ended iterator over all elements in the table
     fn iter(&self) -> Result<Range<K, V>> {
         self.range::<K::SelfType<'_>>(..)
     }
+
+    /// Writes every key-value pair in the table to `writer`, in key order
+    ///
+    /// The output is independent of the on-disk page format, and can be loaded into another table
+    /// with [`Table::import`]. It consists of the number of entries as a little-endian `u64`,
+    /// followed by each key and then value, each written as its length as a little-endian `u32`
+    /// followed by its bytes
+    fn export(&self, mut writer: impl Write) -> Result {
+        writer.write_all(&self.len()?.to_le_bytes())?;
+        for entry in self.iter()? {
+            let (key, value) = entry?;
+            for bytes in [
+                K::as_bytes(&key.value()).as_ref(),
+                V::as_bytes(&value.value()).as_ref(),
+            ] {
+                let len: u32 = bytes.len().try_into().unwrap();
+                writer.write_all(&len.to_le_bytes())?;
+                writer.write_all(bytes)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 /// A read-only table
@@ -286,13 +759,101 @@ impl<'txn, K: RedbKey + 'static, V: RedbValue + 'static> ReadableTable<K, V>
         self.tree.len()
     }
 
+    fn count_range<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.tree.count_range(&range)
+    }
+
+    fn select_nth(&self, n: u64) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        self.tree.select_nth(n)
+    }
+
+    fn split_keys(&self, n: u64) -> Result<Vec<AccessGuard<K>>> {
+        self.tree.split_keys(n)
+    }
+
+    fn separator_keys(&self, levels: usize) -> Result<Vec<AccessGuard<K>>> {
+        self.tree.separator_keys(levels)
+    }
+
     fn is_empty(&self) -> Result<bool> {
-        self.len().map(|x| x == 0)
+        // Removing the last entry frees the root page, so only an empty table has no root
+        Ok(self.tree.get_root().is_none())
+    }
+
+    fn verify_structure(&self) -> Result {
+        self.tree.verify_structure()
+    }
+
+    #[cfg(feature = "serde")]
+    fn tree_structure(&self) -> Result<Option<crate::TreeNode>> {
+        self.tree.tree_structure()
+    }
+
+    fn stats(&self) -> Result<TableStats> {
+        let stats = self.tree.stats()?;
+        Ok(TableStats {
+            tree_height: stats.tree_height,
+            pages_per_level: stats.pages_per_level,
+            leaf_pages: stats.leaf_pages,
+            branch_pages: stats.branch_pages,
+            stored_leaf_bytes: stats.stored_leaf_bytes,
+            metadata_bytes: stats.metadata_bytes,
+            fragmented_bytes: stats.fragmented_bytes,
+        })
     }
 }
 
 impl<K: RedbKey, V: RedbValue> Sealed for ReadOnlyTable<'_, K, V> {}
 
+// Checks that data is a valid encoding of T, so that it can be compared and decoded without
+// panicking
+fn check_encoded<T: RedbValue>(data: &[u8]) -> Result {
+    if let Some(width) = T::fixed_width() {
+        if data.len() != width {
+            return Err(StorageError::Corrupted(format!(
+                "Imported {} has length {}, but expected {width}",
+                T::type_name().name(),
+                data.len()
+            )));
+        }
+    }
+    T::try_from_bytes(data)?;
+    Ok(())
+}
+
+fn read_import_bytes(reader: &mut impl Read, buffer: &mut [u8]) -> Result {
+    reader.read_exact(buffer).map_err(|err| {
+        if err.kind() == ErrorKind::UnexpectedEof {
+            StorageError::Corrupted("Import input is truncated".to_string())
+        } else {
+            err.into()
+        }
+    })
+}
+
+fn read_length_prefixed(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut buffer = [0u8; 4];
+    read_import_bytes(reader, &mut buffer)?;
+    let len: usize = u32::from_le_bytes(buffer).try_into().unwrap();
+    if len > MAX_VALUE_LENGTH {
+        return Err(StorageError::ValueTooLarge(len));
+    }
+    // The buffer grows as data is read, rather than being allocated up front, so that a corrupted
+    // length can't cause a huge allocation
+    let mut data = vec![];
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(StorageError::Corrupted(
+            "Import input is truncated".to_string(),
+        ));
+    }
+    Ok(data)
+}
+
 pub struct Drain<'a, K: RedbKey + 'static, V: RedbValue + 'static> {
     inner: BtreeDrain<'a, K, V>,
 }
@@ -396,6 +957,72 @@ impl<'a, K: RedbKey + 'static, V: RedbValue + 'static> Range<'a, K, V> {
     fn new(inner: BtreeRangeIter<'a, K, V>) -> Self {
         Self { inner }
     }
+
+    /// Advances the front of the iterator, so that the next call to [`Iterator::next`] returns
+    /// the first remaining entry whose key is greater than or equal to `key`
+    ///
+    /// This never moves the iterator backwards, and entries outside of the range are still
+    /// excluded
+    pub fn seek<'k>(&mut self, key: impl Borrow<K::SelfType<'k>>) -> Result
+    where
+        K: 'k,
+    {
+        self.inner.seek(K::as_bytes(key.borrow()).as_ref())
+    }
+
+    /// Moves the back of the iterator, so that the next call to
+    /// [`DoubleEndedIterator::next_back`] returns the last remaining entry whose key is less than
+    /// or equal to `key`
+    ///
+    /// This never moves the iterator forwards, and entries outside of the range are still
+    /// excluded
+    pub fn seek_back<'k>(&mut self, key: impl Borrow<K::SelfType<'k>>) -> Result
+    where
+        K: 'k,
+    {
+        self.inner.seek_back(K::as_bytes(key.borrow()).as_ref())
+    }
+
+    /// Converts this iterator into one which only yields the keys of the remaining entries
+    pub fn keys(self) -> Keys<'a, K, V> {
+        Keys { inner: self.inner }
+    }
+
+    /// Converts this iterator into one which only yields the values of the remaining entries
+    pub fn values(self) -> Values<'a, K, V> {
+        Values { inner: self.inner }
+    }
+
+    /// Converts this iterator into one which passes the raw bytes of each remaining key and value
+    /// to `f`, and yields its result
+    ///
+    /// `f` is called before the iterator advances, so the bytes can be decoded without copying
+    /// them, or implementing [`RedbValue`] for the result
+    pub fn map_entries<R, F: FnMut(&[u8], &[u8]) -> R>(self, f: F) -> MapEntries<'a, K, V, F> {
+        MapEntries {
+            inner: self.inner,
+            f,
+        }
+    }
+
+    /// Advances the iterator by up to `max` entries, and returns copies of their raw keys and
+    /// values
+    ///
+    /// The copies don't borrow from the database, so they can be held across an `.await`. An empty
+    /// batch is returned once the iterator is exhausted
+    pub fn next_batch(&mut self, max: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut batch = vec![];
+        while batch.len() < max {
+            if let Some(entry) = self.inner.next() {
+                let entry = entry?;
+                let (key, value) = entry.raw_entry();
+                batch.push((key.to_vec(), value.to_vec()));
+            } else {
+                break;
+            }
+        }
+        Ok(batch)
+    }
 }
 
 impl<'a, K: RedbKey + 'static, V: RedbValue + 'static> Iterator for Range<'a, K, V> {
@@ -425,3 +1052,118 @@ impl<'a, K: RedbKey + 'static, V: RedbValue + 'static> DoubleEndedIterator for R
         })
     }
 }
+
+/// An iterator over the keys of a [`Range`], which never reads the values
+pub struct Keys<'a, K: RedbKey + 'static, V: RedbValue + 'static> {
+    inner: BtreeRangeIter<'a, K, V>,
+}
+
+impl<'a, K: RedbKey + 'static, V: RedbValue + 'static> Iterator for Keys<'a, K, V> {
+    type Item = Result<AccessGuard<'a, K>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|x| {
+            x.map(|entry| {
+                let (page, key_range, _) = entry.into_raw();
+                AccessGuard::with_page(page, key_range)
+            })
+        })
+    }
+}
+
+impl<'a, K: RedbKey + 'static, V: RedbValue + 'static> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|x| {
+            x.map(|entry| {
+                let (page, key_range, _) = entry.into_raw();
+                AccessGuard::with_page(page, key_range)
+            })
+        })
+    }
+}
+
+/// An iterator over the values of a [`Range`], in key order
+pub struct Values<'a, K: RedbKey + 'static, V: RedbValue + 'static> {
+    inner: BtreeRangeIter<'a, K, V>,
+}
+
+impl<'a, K: RedbKey + 'static, V: RedbValue + 'static> Iterator for Values<'a, K, V> {
+    type Item = Result<AccessGuard<'a, V>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|x| {
+            x.map(|entry| {
+                let (page, _, value_range) = entry.into_raw();
+                AccessGuard::with_page(page, value_range)
+            })
+        })
+    }
+}
+
+impl<'a, K: RedbKey + 'static, V: RedbValue + 'static> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|x| {
+            x.map(|entry| {
+                let (page, _, value_range) = entry.into_raw();
+                AccessGuard::with_page(page, value_range)
+            })
+        })
+    }
+}
+
+/// Gives mutable access to the values of a range of a [`Table`], one entry at a time, as returned
+/// by [`Table::range_mut`]
+///
+/// This doesn't implement [`Iterator`], because each value must be released before the next one
+/// is accessed
+pub struct RangeMut<'t, 'txn, K: RedbKey + 'static, V: RedbValueMutInPlace + 'static> {
+    tree: &'t mut BtreeMut<'txn, K, V>,
+    position: RangeMutPosition,
+}
+
+impl<'t, 'txn, K: RedbKey + 'static, V: RedbValueMutInPlace + 'static> RangeMut<'t, 'txn, K, V> {
+    /// Returns the next entry in the range, with a mutable reference to its value
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(AccessGuard<K>, AccessGuardMut<V>)>> {
+        self.tree
+            .range_mut_next(&mut self.position)
+            .transpose()
+            .map(|result| result.map(|(key, guard)| (AccessGuard::with_owned_value(key), guard)))
+    }
+}
+
+/// An iterator which maps the raw bytes of each entry of a [`Range`] through a closure
+pub struct MapEntries<'a, K: RedbKey + 'static, V: RedbValue + 'static, F> {
+    inner: BtreeRangeIter<'a, K, V>,
+    f: F,
+}
+
+impl<'a, K: RedbKey + 'static, V: RedbValue + 'static, R, F: FnMut(&[u8], &[u8]) -> R> Iterator
+    for MapEntries<'a, K, V, F>
+{
+    type Item = Result<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let f = &mut self.f;
+        self.inner.next().map(|x| {
+            x.map(|entry| {
+                let (key, value) = entry.raw_entry();
+                f(key, value)
+            })
+        })
+    }
+}
+
+impl<'a, K: RedbKey + 'static, V: RedbValue + 'static, R, F: FnMut(&[u8], &[u8]) -> R>
+    DoubleEndedIterator for MapEntries<'a, K, V, F>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let f = &mut self.f;
+        self.inner.next_back().map(|x| {
+            x.map(|entry| {
+                let (key, value) = entry.raw_entry();
+                f(key, value)
+            })
+        })
+    }
+}
@@ -564,6 +564,17 @@ impl<'db> WriteTransaction<'db> {
         self.durability = durability;
     }
 
+    /// Grows the database file, if needed, so that at least `pages` pages are free
+    ///
+    /// Calling this before inserting a large batch of entries avoids growing the file repeatedly
+    /// while they are inserted. The size of a page is returned by [`DatabaseStats::page_size`]
+    ///
+    /// The file is grown once, to the size needed. Returns an error, without growing the file, if
+    /// the database can't hold that many pages
+    pub fn reserve_pages(&self, pages: u64) -> Result {
+        self.mem.reserve(pages)
+    }
+
     /// Open the given table
     ///
     /// The table will be created if it does not exist
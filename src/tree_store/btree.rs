@@ -1,23 +1,49 @@
 use crate::tree_store::btree_base::{
-    branch_checksum, leaf_checksum, BranchAccessor, BranchMutator, Checksum, LeafAccessor, BRANCH,
-    DEFERRED, LEAF,
+    branch_checksum, check_page_type, leaf_checksum, BranchAccessor, BranchMutator, Checksum,
+    LeafAccessor, PageType, BRANCH, DEFERRED, LEAF,
 };
 use crate::tree_store::btree_iters::BtreeDrain;
 use crate::tree_store::btree_mutator::MutateHelper;
 use crate::tree_store::page_store::{CachePriority, Page, PageImpl, PageMut, TransactionalMemory};
 use crate::tree_store::{
-    AccessGuardMut, AllPageNumbersBtreeIter, BtreeDrainFilter, BtreeRangeIter, PageHint, PageNumber,
+    AccessGuardMut, AllPageNumbersBtreeIter, BtreeDrainFilter, BtreeRangeIter, PageHint,
+    PageNumber, MAX_VALUE_LENGTH,
 };
 use crate::types::{RedbKey, RedbValue, RedbValueMutInPlace};
-use crate::{AccessGuard, Result};
+use crate::{AccessGuard, Result, StorageError};
 #[cfg(feature = "logging")]
 use log::trace;
 use std::borrow::Borrow;
-use std::cmp::max;
+use std::cmp::{max, min, Ordering};
 use std::marker::PhantomData;
-use std::ops::{RangeBounds, RangeFull};
+use std::ops::{Bound, RangeBounds, RangeFull};
 use std::sync::{Arc, Mutex};
 
+// Number of entries removed between calls to the progress callback of remove_range_with_progress()
+const REMOVE_RANGE_CHUNK_LEN: usize = 1024;
+
+// The position of a mutable iteration over a range of a BtreeMut
+pub(crate) struct RangeMutPosition {
+    // Lower bound of the entries which haven't been returned yet
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+    // The leaf of the last entry returned, which was written by this transaction, and the index of
+    // the entry after it
+    leaf: Option<(PageNumber, usize)>,
+}
+
+impl RangeMutPosition {
+    pub(crate) fn new<'a, K: RedbKey + 'a, T: RangeBounds<KR>, KR: Borrow<K::SelfType<'a>>>(
+        range: &T,
+    ) -> Self {
+        Self {
+            start: encode_bound::<K, KR>(range.start_bound()),
+            end: encode_bound::<K, KR>(range.end_bound()),
+            leaf: None,
+        }
+    }
+}
+
 pub(crate) struct BtreeStats {
     pub(crate) tree_height: u32,
     pub(crate) leaf_pages: u64,
@@ -25,6 +51,8 @@ pub(crate) struct BtreeStats {
     pub(crate) stored_leaf_bytes: u64,
     pub(crate) metadata_bytes: u64,
     pub(crate) fragmented_bytes: u64,
+    // Number of pages on each level of the tree, starting from the root
+    pub(crate) pages_per_level: Vec<u64>,
 }
 
 pub(crate) struct UntypedBtreeMut<'a> {
@@ -76,9 +104,9 @@ impl<'a> UntypedBtreeMut<'a> {
         assert!(self.mem.uncommitted(page_number));
         let mut page = self.mem.get_page_mut(page_number)?;
 
-        match page.memory()[0] {
-            LEAF => leaf_checksum(&page, self.key_width, self.value_width),
-            BRANCH => {
+        match check_page_type(&page)? {
+            PageType::Leaf => leaf_checksum(&page, self.key_width, self.value_width),
+            PageType::Branch => {
                 let accessor = BranchAccessor::new(&page, self.key_width);
                 let mut new_children = vec![];
                 for i in 0..accessor.count_children() {
@@ -102,7 +130,6 @@ impl<'a> UntypedBtreeMut<'a> {
 
                 branch_checksum(&page, self.key_width)
             }
-            _ => unreachable!(),
         }
     }
 
@@ -118,15 +145,14 @@ impl<'a> UntypedBtreeMut<'a> {
             }
 
             let page = self.mem.get_page_mut(*page_number)?;
-            match page.memory()[0] {
-                LEAF => {
+            match check_page_type(&page)? {
+                PageType::Leaf => {
                     visitor(page)?;
                 }
-                BRANCH => {
+                PageType::Branch => {
                     drop(page);
                     self.dirty_leaf_visitor_helper(*page_number, &visitor)?;
                 }
-                _ => unreachable!(),
             }
         }
 
@@ -140,11 +166,11 @@ impl<'a> UntypedBtreeMut<'a> {
         assert!(self.mem.uncommitted(page_number));
         let page = self.mem.get_page_mut(page_number)?;
 
-        match page.memory()[0] {
-            LEAF => {
+        match check_page_type(&page)? {
+            PageType::Leaf => {
                 visitor(page)?;
             }
-            BRANCH => {
+            PageType::Branch => {
                 let accessor = BranchAccessor::new(&page, self.key_width);
                 for i in 0..accessor.count_children() {
                     let child_page = accessor.child_page(i).unwrap();
@@ -153,7 +179,6 @@ impl<'a> UntypedBtreeMut<'a> {
                     }
                 }
             }
-            _ => unreachable!(),
         }
 
         Ok(())
@@ -189,12 +214,11 @@ impl<'a> UntypedBtreeMut<'a> {
 
         new_page.memory_mut().copy_from_slice(old_page.memory());
 
-        let node_mem = old_page.memory();
-        match node_mem[0] {
-            LEAF => {
+        match check_page_type(&old_page)? {
+            PageType::Leaf => {
                 // No-op
             }
-            BRANCH => {
+            PageType::Branch => {
                 let accessor = BranchAccessor::new(&old_page, self.key_width);
                 let mut mutator = BranchMutator::new(&mut new_page);
                 for i in 0..accessor.count_children() {
@@ -204,7 +228,6 @@ impl<'a> UntypedBtreeMut<'a> {
                     }
                 }
             }
-            _ => unreachable!(),
         }
 
         let mut freed_pages = self.freed_pages.lock().unwrap();
@@ -259,6 +282,8 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
         );
         tree.finalize_dirty_checksums()?;
         *self.root.lock().unwrap() = tree.get_root();
+        #[cfg(feature = "paranoid")]
+        self.paranoid_verify("finalize_dirty_checksums");
         Ok(())
     }
 
@@ -289,6 +314,8 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
         );
         if tree.relocate()? {
             *self.root.lock().unwrap() = tree.get_root();
+            #[cfg(feature = "paranoid")]
+            self.paranoid_verify("relocate");
             Ok(true)
         } else {
             Ok(false)
@@ -307,14 +334,249 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
             key,
             V::as_bytes(value).as_ref().len()
         );
+        self.insert_raw(K::as_bytes(key).as_ref(), V::as_bytes(value).as_ref())
+    }
+
+    // Inserts an already encoded key and value
+    pub(crate) fn insert_raw(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<AccessGuard<V>>> {
         let mut freed_pages = self.freed_pages.lock().unwrap();
         let mut root = self.root.lock().unwrap();
         let mut operation: MutateHelper<'_, '_, K, V> =
             MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
-        let (old_value, _) = operation.insert(key, value)?;
+        let (old_value, _) = operation.insert_raw(key, value)?;
+        #[cfg(feature = "paranoid")]
+        {
+            drop(operation);
+            drop(root);
+            drop(freed_pages);
+            self.paranoid_verify("insert");
+        }
         Ok(old_value)
     }
 
+    // Inserts the pair if the key is absent, in the same descent that finds whether it's present.
+    // Returns true if the value was inserted
+    pub(crate) fn insert_if_absent(
+        &mut self,
+        key: &K::SelfType<'_>,
+        value: &V::SelfType<'_>,
+    ) -> Result<bool> {
+        #[cfg(feature = "logging")]
+        trace!(
+            "Btree(root={:?}): Inserting {:?} if absent, with value of length {}",
+            &self.root,
+            key,
+            V::as_bytes(value).as_ref().len()
+        );
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        let mut operation: MutateHelper<'_, '_, K, V> =
+            MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
+        let inserted = operation.insert_if_absent(key, value)?;
+        #[cfg(feature = "paranoid")]
+        {
+            drop(operation);
+            drop(root);
+            drop(freed_pages);
+            self.paranoid_verify("insert_if_absent");
+        }
+        Ok(inserted)
+    }
+
+    // Inserts all the pairs using a single mutation operation. Previous values are dropped as soon
+    // as they are replaced, and no guard is created for the inserted values
+    pub(crate) fn insert_many<'k, 'v, KR, VR>(
+        &mut self,
+        entries: impl IntoIterator<Item = (KR, VR)>,
+    ) -> Result
+    where
+        K: 'k,
+        V: 'v,
+        KR: Borrow<K::SelfType<'k>>,
+        VR: Borrow<V::SelfType<'v>>,
+    {
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        let mut operation: MutateHelper<'_, '_, K, V> =
+            MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
+        for (key, value) in entries {
+            let key = key.borrow();
+            let value = value.borrow();
+            let value_len = V::as_bytes(value).as_ref().len();
+            if value_len > MAX_VALUE_LENGTH {
+                return Err(StorageError::ValueTooLarge(value_len));
+            }
+            let key_len = K::as_bytes(key).as_ref().len();
+            if key_len > MAX_VALUE_LENGTH {
+                return Err(StorageError::ValueTooLarge(key_len));
+            }
+            #[cfg(feature = "logging")]
+            trace!(
+                "Btree(root={:?}): Inserting {:?} with value of length {}",
+                &self.root,
+                key,
+                value_len
+            );
+            // The old value must be dropped before the next insert, since it may modify its page
+            drop(operation.insert_without_guard(key, value)?);
+        }
+        #[cfg(feature = "paranoid")]
+        {
+            drop(operation);
+            drop(root);
+            drop(freed_pages);
+            self.paranoid_verify("insert_many");
+        }
+        Ok(())
+    }
+
+    // Inserts entries which are sorted by key. Entries up to the last key already in the tree are
+    // inserted individually, and the remainder are appended by rewriting only the rightmost path
+    pub(crate) fn extend_sorted<'k, 'v, KR, VR>(
+        &mut self,
+        entries: impl IntoIterator<Item = (KR, VR)>,
+    ) -> Result
+    where
+        K: 'k,
+        V: 'v,
+        KR: Borrow<K::SelfType<'k>>,
+        VR: Borrow<V::SelfType<'v>>,
+    {
+        self.extend_sorted_encoded(entries.into_iter().map(|(key, value)| {
+            Ok((
+                K::as_bytes(key.borrow()).as_ref().to_vec(),
+                V::as_bytes(value.borrow()).as_ref().to_vec(),
+            ))
+        }))
+    }
+
+    // Like extend_sorted(), but takes encoded entries. The entries are built into a separate tree
+    // as they're read, so that they're never all held in memory, and so that the table is left
+    // unchanged if one of them is an error. That tree becomes the table if it was empty, and
+    // otherwise is merged into it
+    pub(crate) fn extend_sorted_encoded(
+        &mut self,
+        entries: impl IntoIterator<Item = Result<(Vec<u8>, Vec<u8>)>>,
+    ) -> Result {
+        // Misordered keys are reported as an error while building, and panic once the locks have
+        // been released, so that they aren't poisoned
+        let mut unordered = false;
+        let mut previous_key: Option<Vec<u8>> = None;
+        let entries = entries.into_iter().map(|entry| {
+            let (key_bytes, value_bytes) = entry?;
+            if value_bytes.len() > MAX_VALUE_LENGTH {
+                return Err(StorageError::ValueTooLarge(value_bytes.len()));
+            }
+            if key_bytes.len() > MAX_VALUE_LENGTH {
+                return Err(StorageError::ValueTooLarge(key_bytes.len()));
+            }
+            if let Some(ref previous_key) = previous_key {
+                if !K::compare(previous_key, &key_bytes).is_lt() {
+                    unordered = true;
+                    return Err(StorageError::Corrupted(
+                        "Keys are not in strictly increasing order".to_string(),
+                    ));
+                }
+            }
+            previous_key = Some(key_bytes.clone());
+            Ok((key_bytes, value_bytes))
+        });
+
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        let mut new_root = None;
+        let mut operation: MutateHelper<'_, '_, K, V> =
+            MutateHelper::new(&mut new_root, self.mem, freed_pages.as_mut());
+        if let Err(err) = operation.append_sorted(entries) {
+            drop(root);
+            drop(freed_pages);
+            assert!(
+                !unordered,
+                "extend_sorted() requires keys in strictly increasing order"
+            );
+            return Err(err);
+        }
+        let result = match (*root, new_root) {
+            (_, None) => Ok(()),
+            (None, Some(_)) => {
+                *root = new_root;
+                Ok(())
+            }
+            (Some((existing_root, _)), Some((new_root, _))) => {
+                let new_pages = AllPageNumbersBtreeIter::new(
+                    new_root,
+                    K::fixed_width(),
+                    V::fixed_width(),
+                    self.mem,
+                )?
+                .collect::<Result<Vec<PageNumber>>>()?;
+                let mut operation: MutateHelper<'_, '_, K, V> =
+                    MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
+                let result = Self::merge_sorted(&mut operation, existing_root, new_root, self.mem);
+                // The new tree's entries have been copied into the table, or failed to be
+                for page in new_pages {
+                    self.mem.free(page);
+                }
+                result
+            }
+        };
+        #[cfg(feature = "paranoid")]
+        {
+            drop(root);
+            drop(freed_pages);
+            self.paranoid_verify("extend_sorted");
+        }
+
+        result
+    }
+
+    // Merges the tree rooted at new_root into the one being modified by operation. Entries up to
+    // the last key in that tree are inserted individually, and the remainder are appended by
+    // rewriting only its rightmost path
+    fn merge_sorted(
+        operation: &mut MutateHelper<'_, '_, K, V>,
+        existing_root: PageNumber,
+        new_root: PageNumber,
+        mem: &'a TransactionalMemory,
+    ) -> Result {
+        let last_key = last_key_in_subtree::<K, V>(existing_root, mem)?;
+        let interleaved: BtreeRangeIter<K, V> = BtreeRangeIter::new(
+            &(Bound::Unbounded, Bound::Included(K::from_bytes(&last_key))),
+            Some(new_root),
+            mem,
+        )?;
+        for entry in interleaved {
+            let (page, key_range, value_range) = entry?.into_raw();
+            let (key, value) = (&page.memory()[key_range], &page.memory()[value_range]);
+            #[cfg(feature = "logging")]
+            trace!(
+                "Btree: Inserting {:?} with value of length {}",
+                K::from_bytes(key),
+                value.len()
+            );
+            // Both guards must be dropped before the next insert, since it may modify their pages
+            let (old_value, guard) = operation.insert_raw(key, value)?;
+            drop(guard);
+            drop(old_value);
+        }
+        let appended: BtreeRangeIter<K, V> = BtreeRangeIter::new(
+            &(Bound::Excluded(K::from_bytes(&last_key)), Bound::Unbounded),
+            Some(new_root),
+            mem,
+        )?;
+        operation.append_sorted(appended.map(|entry| {
+            let (page, key_range, value_range) = entry?.into_raw();
+            Ok((
+                page.memory()[key_range].to_vec(),
+                page.memory()[value_range].to_vec(),
+            ))
+        }))
+    }
+
     pub(crate) fn remove(&mut self, key: &K::SelfType<'_>) -> Result<Option<AccessGuard<V>>> {
         #[cfg(feature = "logging")]
         trace!("Btree(root={:?}): Deleting {:?}", &self.root, key);
@@ -323,9 +585,132 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
         let mut operation: MutateHelper<'_, '_, K, V> =
             MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
         let result = operation.delete(key)?;
+        #[cfg(feature = "paranoid")]
+        let result = {
+            drop(operation);
+            drop(root);
+            drop(freed_pages);
+            // Removing an entry from a dirty page is deferred until its guard is dropped
+            let result = result.map(AccessGuard::into_owned);
+            self.paranoid_verify("remove");
+            result
+        };
         Ok(result)
     }
 
+    pub(crate) fn clear(&mut self) -> Result {
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        if let Some((root_page, _)) = *root {
+            // Collect the page numbers first, so that no pages are referenced while they're freed,
+            // and so that the tree is left intact if reading them fails
+            let pages = AllPageNumbersBtreeIter::new(
+                root_page,
+                K::fixed_width(),
+                V::fixed_width(),
+                self.mem,
+            )?
+            .collect::<Result<Vec<PageNumber>>>()?;
+            *root = None;
+            for page in pages {
+                if !self.mem.free_if_uncommitted(page) {
+                    freed_pages.push(page);
+                }
+            }
+        }
+        #[cfg(feature = "paranoid")]
+        {
+            drop(root);
+            drop(freed_pages);
+            self.paranoid_verify("clear");
+        }
+
+        Ok(())
+    }
+
+    // Rebuilds the tree from its entries, with every page filled
+    pub(crate) fn compact(&mut self) -> Result {
+        self.rebuild(None)?;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_verify("compact");
+
+        Ok(())
+    }
+
+    // Rebuilds the tree from the entries for which predicate returns true, with every page filled
+    pub(crate) fn retain<F: for<'f> FnMut(K::SelfType<'f>, V::SelfType<'f>) -> bool>(
+        &mut self,
+        mut predicate: F,
+    ) -> Result {
+        // The predicate is run before taking any locks, so that a panic in it can't poison them
+        let mut keep = vec![];
+        for entry in self.range::<RangeFull, K::SelfType<'_>>(&(..))? {
+            let entry = entry?;
+            keep.push(predicate(entry.key(), entry.value()));
+        }
+        self.rebuild(Some(&keep))?;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_verify("retain");
+
+        Ok(())
+    }
+
+    // Rebuilds the tree from its entries, with every page filled. If keep is given, it has an
+    // element for each entry, in order, which is false if that entry should be dropped
+    fn rebuild(&mut self, keep: Option<&[bool]>) -> Result {
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        let old_root = if let Some((p, _)) = *root {
+            p
+        } else {
+            return Ok(());
+        };
+        let old_pages =
+            AllPageNumbersBtreeIter::new(old_root, K::fixed_width(), V::fixed_width(), self.mem)?
+                .collect::<Result<Vec<PageNumber>>>()?;
+
+        // The old pages are only read while the new tree is built, so must be freed afterwards
+        let iter: BtreeRangeIter<K, V> =
+            BtreeRangeIter::new::<RangeFull, K::SelfType<'_>>(&(..), Some(old_root), self.mem)?;
+        let entries = iter.enumerate().filter_map(|(i, entry)| match entry {
+            Ok(entry) => {
+                if keep.map_or(true, |keep| keep[i]) {
+                    let (page, key_range, value_range) = entry.into_raw();
+                    Some(Ok((
+                        page.memory()[key_range].to_vec(),
+                        page.memory()[value_range].to_vec(),
+                    )))
+                } else {
+                    None
+                }
+            }
+            Err(err) => Some(Err(err)),
+        });
+        // The new tree is built separately, so that the old one is left in place if that fails
+        let mut new_root = None;
+        let mut operation: MutateHelper<'_, '_, K, V> =
+            MutateHelper::new(&mut new_root, self.mem, freed_pages.as_mut());
+        operation.append_sorted(entries)?;
+        *root = new_root;
+
+        for page in old_pages {
+            if !self.mem.free_if_uncommitted(page) {
+                freed_pages.push(page);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Verifies the tree after a modification, so that a bug is caught by the operation which
+    // caused it, rather than by a later one. Must not be called while holding the tree's locks
+    #[cfg(feature = "paranoid")]
+    fn paranoid_verify(&self, operation: &str) {
+        if let Err(err) = self.verify_structure() {
+            panic!("Btree invariant violated after {operation}(): {err}");
+        }
+    }
+
     #[allow(dead_code)]
     pub(crate) fn print_debug(&self, include_values: bool) -> Result {
         self.read_tree()?.print_debug(include_values)
@@ -348,6 +733,77 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
         self.read_tree()?.get(key)
     }
 
+    pub(crate) fn select_nth(
+        &self,
+        n: u64,
+    ) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
+        self.read_tree()?.select_nth(n)
+    }
+
+    pub(crate) fn split_keys(&self, n: u64) -> Result<Vec<AccessGuard<'_, K>>> {
+        self.read_tree()?.split_keys(n)
+    }
+
+    pub(crate) fn separator_keys(&self, levels: usize) -> Result<Vec<AccessGuard<'_, K>>> {
+        self.read_tree()?.separator_keys(levels)
+    }
+
+    // Returns the value for the key, first inserting the value returned by f if the key is absent.
+    // The key is looked up and the value inserted in a single descent, and f is only called once
+    // the leaf for the key is found not to contain it
+    pub(crate) fn get_or_insert_with<'v, VR: Borrow<V::SelfType<'v>>>(
+        &mut self,
+        key: &K::SelfType<'_>,
+        f: impl FnOnce() -> VR,
+    ) -> Result<AccessGuard<'_, V>>
+    where
+        V: 'v,
+    {
+        let key_bytes = K::as_bytes(key);
+        let key_bytes = key_bytes.as_ref();
+        if key_bytes.len() > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(key_bytes.len()));
+        }
+        let mut f = Some(f);
+        let mut compute_value = || {
+            let value = f.take().unwrap()();
+            let value_bytes = V::as_bytes(value.borrow()).as_ref().to_vec();
+            if value_bytes.len() > MAX_VALUE_LENGTH {
+                return Err(StorageError::ValueTooLarge(value_bytes.len()));
+            }
+            Ok(value_bytes)
+        };
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        let mut operation: MutateHelper<'_, '_, K, V> =
+            MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
+        let (existing, inserted) =
+            operation.insert_if_absent_with(key_bytes, &mut compute_value)?;
+        // The guard for the inserted value holds its page mutably, so it's released, and a
+        // read-only guard is created for the same value below
+        let inserted = inserted.map(AccessGuardMut::into_location);
+        drop(operation);
+        drop(root);
+        drop(freed_pages);
+        #[cfg(feature = "paranoid")]
+        self.paranoid_verify("get_or_insert_with");
+        if let Some(existing) = existing {
+            return Ok(existing);
+        }
+        let (page_number, offset, len) = inserted.unwrap();
+        let page = self.mem.get_page(page_number)?;
+        Ok(AccessGuard::new(page, offset, len, false, self.mem))
+    }
+
+    pub(crate) fn verify_structure(&self) -> Result {
+        self.read_tree()?.verify_structure()
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn tree_structure(&self) -> Result<Option<TreeNode>> {
+        self.read_tree()?.tree_structure()
+    }
+
     pub(crate) fn range<'a0, T: RangeBounds<KR> + 'a0, KR: Borrow<K::SelfType<'a0>> + 'a0>(
         &self,
         range: &'_ T,
@@ -358,6 +814,100 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
         self.read_tree()?.range(range)
     }
 
+    pub(crate) fn remove_range<'a0, T: RangeBounds<KR> + 'a0, KR: Borrow<K::SelfType<'a0>> + 'a0>(
+        &mut self,
+        range: &'_ T,
+    ) -> Result<u64>
+    where
+        K: 'a0,
+    {
+        let start = encode_bound::<K, KR>(range.start_bound());
+        let end = encode_bound::<K, KR>(range.end_bound());
+        // A range which contains no keys, such as 5..5, removes nothing
+        if let (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e)) =
+            (&start, &end)
+        {
+            match K::compare(s, e) {
+                Ordering::Greater => return Ok(0),
+                Ordering::Equal
+                    if !matches!((&start, &end), (Bound::Included(_), Bound::Included(_))) =>
+                {
+                    return Ok(0)
+                }
+                _ => {}
+            }
+        }
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        let mut operation: MutateHelper<'_, '_, K, V> =
+            MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
+        let removed = operation.delete_range(as_slice_bound(&start), as_slice_bound(&end))?;
+        #[cfg(feature = "paranoid")]
+        {
+            drop(operation);
+            drop(root);
+            drop(freed_pages);
+            self.paranoid_verify("remove_range");
+        }
+
+        Ok(removed)
+    }
+
+    // Removes the entries in range in ascending order, REMOVE_RANGE_CHUNK_LEN at a time, calling
+    // progress with the number removed so far after each chunk, and stopping early if it returns
+    // false
+    pub(crate) fn remove_range_with_progress<
+        'a0,
+        T: RangeBounds<KR> + 'a0,
+        KR: Borrow<K::SelfType<'a0>> + 'a0,
+        F: FnMut(u64) -> bool,
+    >(
+        &mut self,
+        range: &'_ T,
+        mut progress: F,
+    ) -> Result<u64>
+    where
+        K: 'a0,
+    {
+        let mut start = encode_bound::<K, KR>(range.start_bound());
+        let end = encode_bound::<K, KR>(range.end_bound());
+        let mut removed = 0;
+        loop {
+            // Find the last key of the next chunk. The range iterator holds references to pages
+            // that will be modified or freed, so must be dropped before the chunk is removed
+            let mut last = None;
+            for entry in self
+                .range(&(decode_bound::<K>(&start), decode_bound::<K>(&end)))?
+                .take(REMOVE_RANGE_CHUNK_LEN)
+            {
+                last = Some(entry?.key_data());
+            }
+            let last = if let Some(last) = last {
+                last
+            } else {
+                break;
+            };
+            // The locks are released before calling progress, so that a panic in it can't poison
+            // them
+            {
+                let mut freed_pages = self.freed_pages.lock().unwrap();
+                let mut root = self.root.lock().unwrap();
+                let mut operation: MutateHelper<'_, '_, K, V> =
+                    MutateHelper::new(&mut root, self.mem, freed_pages.as_mut());
+                removed += operation
+                    .delete_range(as_slice_bound(&start), Bound::Included(last.as_slice()))?;
+            }
+            #[cfg(feature = "paranoid")]
+            self.paranoid_verify("remove_range_with_progress");
+            start = Bound::Excluded(last);
+            if !progress(removed) {
+                break;
+            }
+        }
+
+        Ok(removed)
+    }
+
     pub(crate) fn drain<'a0, T: RangeBounds<KR> + 'a0, KR: Borrow<K::SelfType<'a0>> + 'a0>(
         &mut self,
         range: &'_ T,
@@ -375,6 +925,12 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
             // TODO: optimize so that we don't have to call delete in a loop
             assert!(operation.delete(&entry?.key())?.is_some());
         }
+        #[cfg(feature = "paranoid")]
+        {
+            drop(operation);
+            drop(root);
+            self.paranoid_verify("drain");
+        }
 
         let result = BtreeDrain::new(
             return_iter,
@@ -412,6 +968,12 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
                 assert!(operation.delete(&entry.key())?.is_some());
             }
         }
+        #[cfg(feature = "paranoid")]
+        {
+            drop(operation);
+            drop(root);
+            self.paranoid_verify("drain_filter");
+        }
 
         let result = BtreeDrainFilter::new(
             return_iter,
@@ -427,6 +989,16 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeMut<'a, K, V> {
     pub(crate) fn len(&self) -> Result<u64> {
         self.read_tree()?.len()
     }
+
+    pub(crate) fn count_range<'a0, T: RangeBounds<KR> + 'a0, KR: Borrow<K::SelfType<'a0>> + 'a0>(
+        &self,
+        range: &'_ T,
+    ) -> Result<u64>
+    where
+        K: 'a0,
+    {
+        self.read_tree()?.count_range(range)
+    }
 }
 
 impl<'a, K: RedbKey + 'a, V: RedbValueMutInPlace + 'a> BtreeMut<'a, K, V> {
@@ -452,8 +1024,147 @@ impl<'a, K: RedbKey + 'a, V: RedbValueMutInPlace + 'a> BtreeMut<'a, K, V> {
         let mut operation = MutateHelper::<K, V>::new(&mut root, self.mem, freed_pages.as_mut());
         let (_, guard) = operation.insert(key, &V::from_bytes(&value))?;
         drop(root);
+        #[cfg(feature = "paranoid")]
+        let guard = {
+            drop(freed_pages);
+            self.paranoid_verify_with_guard("insert_reserve", key, guard)?
+        };
         Ok(guard)
     }
+
+    pub(crate) fn append_value(&mut self, key: &K::SelfType<'_>, extra: &[u8]) -> Result {
+        #[cfg(feature = "logging")]
+        trace!(
+            "Btree(root={:?}): Appending {} bytes to the value of {:?}",
+            &self.root,
+            extra.len(),
+            key
+        );
+        let mut value = if let Some(guard) = self.get(key)? {
+            V::as_bytes(&guard.value()).as_ref().to_vec()
+        } else {
+            vec![]
+        };
+        if value.len() + extra.len() > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(value.len() + extra.len()));
+        }
+        value.extend_from_slice(extra);
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        let mut operation = MutateHelper::<K, V>::new(&mut root, self.mem, freed_pages.as_mut());
+        drop(operation.insert_without_guard(key, &V::from_bytes(&value))?);
+        #[cfg(feature = "paranoid")]
+        {
+            drop(operation);
+            drop(root);
+            drop(freed_pages);
+            self.paranoid_verify("append_value");
+        }
+        Ok(())
+    }
+
+    // Return type has the same lifetime as &self, because the tree must not be modified until the mutable guard is dropped
+    pub(crate) fn get_mut(&mut self, key: &K::SelfType<'_>) -> Result<Option<AccessGuardMut<V>>> {
+        #[cfg(feature = "logging")]
+        trace!(
+            "Btree(root={:?}): Getting {:?} for mutation",
+            &self.root,
+            key
+        );
+        let (page_number, offset, len) =
+            if let Some(location) = self.read_tree()?.value_location(key)? {
+                location
+            } else {
+                return Ok(None);
+            };
+        // A leaf which was written by this transaction can be modified where it is. Every page
+        // above it was also written by this transaction, and their checksums are only computed
+        // when it commits
+        if self.mem.uncommitted(page_number) {
+            let page = self.mem.get_page_mut(page_number)?;
+            return Ok(Some(AccessGuardMut::new(page, offset, len)));
+        }
+        let value = self.mem.get_page(page_number)?.memory()[offset..(offset + len)].to_vec();
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut root = self.root.lock().unwrap();
+        // Otherwise the leaf is shared with the last commit, so rewrite the existing value, which
+        // copies the pages on its path
+        let mut operation = MutateHelper::<K, V>::new(&mut root, self.mem, freed_pages.as_mut());
+        let (_, guard) = operation.insert(key, &V::from_bytes(&value))?;
+        drop(root);
+        #[cfg(feature = "paranoid")]
+        let guard = {
+            drop(freed_pages);
+            self.paranoid_verify_with_guard("get_mut", key, guard)?
+        };
+        Ok(Some(guard))
+    }
+
+    // Returns the next entry within the range of position, with mutable access to its value. Only
+    // the first entry in each leaf is found from the root, and the path to it is rewritten if it
+    // was written by an earlier transaction, so that the rest of the leaf's entries can then be
+    // modified in place
+    pub(crate) fn range_mut_next(
+        &mut self,
+        position: &mut RangeMutPosition,
+    ) -> Result<Option<(Vec<u8>, AccessGuardMut<V>)>> {
+        loop {
+            if let Some((page_number, entry_index)) = position.leaf {
+                let page = self.mem.get_page(page_number)?;
+                let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                if entry_index < accessor.num_pairs() {
+                    let key = accessor.entry(entry_index).unwrap().key().to_vec();
+                    let in_range = match &position.end {
+                        Bound::Included(end) => K::compare(&key, end).is_le(),
+                        Bound::Excluded(end) => K::compare(&key, end).is_lt(),
+                        Bound::Unbounded => true,
+                    };
+                    if !in_range {
+                        return Ok(None);
+                    }
+                    let (start, end) = accessor.value_range(entry_index).unwrap();
+                    drop(page);
+                    position.start = Bound::Excluded(key.clone());
+                    position.leaf = Some((page_number, entry_index + 1));
+                    let page = self.mem.get_page_mut(page_number)?;
+                    return Ok(Some((key, AccessGuardMut::new(page, start, end - start))));
+                }
+                // The rest of the range is in the following leaves
+                position.leaf = None;
+            }
+
+            let key = if let Some(entry) = self
+                .range(&(
+                    decode_bound::<K>(&position.start),
+                    decode_bound::<K>(&position.end),
+                ))?
+                .next()
+            {
+                entry?.key_data()
+            } else {
+                return Ok(None);
+            };
+            drop(self.get_mut(&K::from_bytes(&key))?);
+            position.leaf = self.read_tree()?.entry_location(&K::from_bytes(&key))?;
+            assert!(position.leaf.is_some());
+        }
+    }
+
+    // Like paranoid_verify(), for an insertion whose guard is returned. The guard holds its page
+    // mutably, so it's dropped during the verification, and then created again
+    #[cfg(feature = "paranoid")]
+    fn paranoid_verify_with_guard(
+        &self,
+        operation: &str,
+        key: &K::SelfType<'_>,
+        guard: AccessGuardMut<'a, V>,
+    ) -> Result<AccessGuardMut<'a, V>> {
+        drop(guard);
+        self.paranoid_verify(operation);
+        let (page_number, offset, len) = self.read_tree()?.value_location(key)?.unwrap();
+        let page = self.mem.get_page_mut(page_number)?;
+        Ok(AccessGuardMut::new(page, offset, len))
+    }
 }
 
 pub(crate) struct RawBtree<'a> {
@@ -558,6 +1269,10 @@ impl<'a, K: RedbKey, V: RedbValue> Btree<'a, K, V> {
         })
     }
 
+    pub(crate) fn get_root(&self) -> Option<(PageNumber, Checksum)> {
+        self.root
+    }
+
     pub(crate) fn get(&self, key: &K::SelfType<'_>) -> Result<Option<AccessGuard<'a, V>>> {
         if let Some(ref root_page) = self.cached_root {
             self.get_helper(root_page.clone(), K::as_bytes(key).as_ref())
@@ -566,11 +1281,170 @@ impl<'a, K: RedbKey, V: RedbValue> Btree<'a, K, V> {
         }
     }
 
+    pub(crate) fn select_nth(
+        &self,
+        n: u64,
+    ) -> Result<Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        if let Some(ref root_page) = self.cached_root {
+            self.select_nth_helper(root_page.clone(), n)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Returns up to n - 1 keys which divide the tree into n ranges with roughly equal numbers of
+    // entries. The entries of every leaf are counted in a single pass, and then only the leaves
+    // which hold the returned keys are read again
+    pub(crate) fn split_keys(&self, n: u64) -> Result<Vec<AccessGuard<'a, K>>> {
+        let root = if let Some(ref root_page) = self.cached_root {
+            root_page.clone()
+        } else {
+            return Ok(vec![]);
+        };
+        let mut leaves = vec![];
+        self.leaf_lengths_helper(root, &mut leaves)?;
+        let len: u64 = leaves.iter().map(|(_, leaf_len)| leaf_len).sum();
+
+        let mut result = vec![];
+        let mut previous_rank = 0;
+        // The leaf holding the current rank, and the rank of its first entry
+        let mut leaf_index = 0;
+        let mut leaf_start = 0;
+        for i in 1..n {
+            let rank: u64 = (u128::from(i) * u128::from(len) / u128::from(n))
+                .try_into()
+                .unwrap();
+            if rank == previous_rank {
+                continue;
+            }
+            previous_rank = rank;
+            while leaf_start + leaves[leaf_index].1 <= rank {
+                leaf_start += leaves[leaf_index].1;
+                leaf_index += 1;
+            }
+            let page = self
+                .mem
+                .get_page_extended(leaves[leaf_index].0, self.hint)?;
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            let (key_range, _) = accessor
+                .entry_ranges((rank - leaf_start).try_into().unwrap())
+                .unwrap();
+            drop(accessor);
+            result.push(AccessGuard::with_page(page, key_range));
+        }
+        Ok(result)
+    }
+
+    // Appends the page number and number of entries of each leaf in the subtree, in order
+    fn leaf_lengths_helper(
+        &self,
+        page: PageImpl<'a>,
+        leaves: &mut Vec<(PageNumber, u64)>,
+    ) -> Result {
+        if check_page_type(&page)? == PageType::Leaf {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            leaves.push((
+                page.get_page_number(),
+                accessor.num_pairs().try_into().unwrap(),
+            ));
+        } else {
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            for i in 0..accessor.count_children() {
+                let child = accessor.child_page(i).unwrap();
+                let child_page = self.mem.get_page_extended(child, self.hint)?;
+                self.leaf_lengths_helper(child_page, leaves)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the separator keys of the branch pages on the first `levels` levels of the tree, in
+    // order. No leaves are read, except for the leftmost one, which gives the height of the tree
+    pub(crate) fn separator_keys(&self, levels: usize) -> Result<Vec<AccessGuard<'a, K>>> {
+        let root = if let Some(ref root_page) = self.cached_root {
+            root_page.clone()
+        } else {
+            return Ok(vec![]);
+        };
+        // The tree is balanced, so every path from the root to a leaf has the same length
+        let mut branch_levels = 0;
+        let mut page = root.clone();
+        while check_page_type(&page)? == PageType::Branch {
+            let child = BranchAccessor::new(&page, K::fixed_width())
+                .child_page(0)
+                .unwrap();
+            page = self.mem.get_page_extended(child, self.hint)?;
+            branch_levels += 1;
+        }
+        let mut result = vec![];
+        self.separator_keys_helper(root, min(levels, branch_levels), &mut result)?;
+        Ok(result)
+    }
+
+    // Appends the separator keys of the branch page, interleaved with those of its descendants on
+    // the next levels - 1 levels
+    fn separator_keys_helper(
+        &self,
+        page: PageImpl<'a>,
+        levels: usize,
+        result: &mut Vec<AccessGuard<'a, K>>,
+    ) -> Result {
+        if levels == 0 {
+            return Ok(());
+        }
+        let accessor = BranchAccessor::new(&page, K::fixed_width());
+        for i in 0..accessor.count_children() {
+            if levels > 1 {
+                let child = accessor.child_page(i).unwrap();
+                let child_page = self.mem.get_page_extended(child, self.hint)?;
+                self.separator_keys_helper(child_page, levels - 1, result)?;
+            }
+            if let Some(key) = accessor.key(i) {
+                result.push(AccessGuard::with_owned_value(key.to_vec()));
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the n'th entry of the subtree. The entries of each child before the one which holds
+    // it are counted, which reads every leaf before the n'th entry
+    fn select_nth_helper(
+        &self,
+        page: PageImpl<'a>,
+        mut n: u64,
+    ) -> Result<Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        if check_page_type(&page)? == PageType::Leaf {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            let ranges = usize::try_from(n)
+                .ok()
+                .and_then(|index| accessor.entry_ranges(index));
+            if let Some((key_range, value_range)) = ranges {
+                let key = AccessGuard::with_page(page.clone(), key_range);
+                let value = AccessGuard::with_page(page, value_range);
+                Ok(Some((key, value)))
+            } else {
+                Ok(None)
+            }
+        } else {
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            for i in 0..accessor.count_children() {
+                let child = accessor.child_page(i).unwrap();
+                let child_len =
+                    self.count_range_helper(child, Bound::Unbounded, Bound::Unbounded)?;
+                if n < child_len {
+                    let child_page = self.mem.get_page_extended(child, self.hint)?;
+                    return self.select_nth_helper(child_page, n);
+                }
+                n -= child_len;
+            }
+            Ok(None)
+        }
+    }
+
     // Returns the value for the queried key, if present
     fn get_helper(&self, page: PageImpl<'a>, query: &[u8]) -> Result<Option<AccessGuard<'a, V>>> {
-        let node_mem = page.memory();
-        match node_mem[0] {
-            LEAF => {
+        match check_page_type(&page)? {
+            PageType::Leaf => {
                 let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
                 if let Some(entry_index) = accessor.find_key::<K>(query) {
                     let (start, end) = accessor.value_range(entry_index).unwrap();
@@ -581,12 +1455,47 @@ impl<'a, K: RedbKey, V: RedbValue> Btree<'a, K, V> {
                     Ok(None)
                 }
             }
-            BRANCH => {
+            PageType::Branch => {
                 let accessor = BranchAccessor::new(&page, K::fixed_width());
                 let (_, child_page) = accessor.child_for_key::<K>(query);
                 self.get_helper(self.mem.get_page_extended(child_page, self.hint)?, query)
             }
-            _ => unreachable!(),
+        }
+    }
+
+    // Returns the page, offset, and length of the value for the key, if it's present
+    fn value_location(&self, key: &K::SelfType<'_>) -> Result<Option<(PageNumber, usize, usize)>> {
+        Ok(self.find_entry(key)?.map(|(page, entry_index)| {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            let (start, end) = accessor.value_range(entry_index).unwrap();
+            (page.get_page_number(), start, end - start)
+        }))
+    }
+
+    // Returns the leaf which holds the key, and the index of its entry in it, if it's present
+    fn entry_location(&self, key: &K::SelfType<'_>) -> Result<Option<(PageNumber, usize)>> {
+        Ok(self
+            .find_entry(key)?
+            .map(|(page, entry_index)| (page.get_page_number(), entry_index)))
+    }
+
+    fn find_entry(&self, key: &K::SelfType<'_>) -> Result<Option<(PageImpl<'a>, usize)>> {
+        let query = K::as_bytes(key);
+        let query = query.as_ref();
+        let mut page_number = if let Some((p, _)) = self.root {
+            p
+        } else {
+            return Ok(None);
+        };
+        loop {
+            let page = self.mem.get_page_extended(page_number, self.hint)?;
+            if check_page_type(&page)? == PageType::Leaf {
+                let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                let entry_index = accessor.find_key::<K>(query);
+                return Ok(entry_index.map(|entry_index| (page, entry_index)));
+            }
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            page_number = accessor.child_for_key::<K>(query).1;
         }
     }
 
@@ -601,17 +1510,116 @@ impl<'a, K: RedbKey, V: RedbValue> Btree<'a, K, V> {
     }
 
     pub(crate) fn len(&self) -> Result<u64> {
-        let iter: BtreeRangeIter<K, V> = BtreeRangeIter::new::<RangeFull, K::SelfType<'_>>(
-            &(..),
+        if let Some((p, _)) = self.root {
+            self.count_range_helper(p, Bound::Unbounded, Bound::Unbounded)
+        } else {
+            Ok(0)
+        }
+    }
+
+    pub(crate) fn count_range<'a0, T: RangeBounds<KR> + 'a0, KR: Borrow<K::SelfType<'a0>> + 'a0>(
+        &self,
+        range: &'_ T,
+    ) -> Result<u64>
+    where
+        K: 'a0,
+    {
+        let start = encode_bound::<K, KR>(range.start_bound());
+        let end = encode_bound::<K, KR>(range.end_bound());
+        if let Some((p, _)) = self.root {
+            self.count_range_helper(p, as_slice_bound(&start), as_slice_bound(&end))
+        } else {
+            Ok(0)
+        }
+    }
+
+    // Counts the entries in the subtree which are within the given bounds. Subtrees which are
+    // entirely within the bounds are counted a leaf at a time
+    fn count_range_helper(
+        &self,
+        page_number: PageNumber,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result<u64> {
+        let page = self.mem.get_page(page_number)?;
+        match check_page_type(&page)? {
+            PageType::Leaf => {
+                let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                let first = match start {
+                    Bound::Included(k) => accessor.position::<K>(k).0,
+                    Bound::Excluded(k) => {
+                        let (position, found) = accessor.position::<K>(k);
+                        if found {
+                            position + 1
+                        } else {
+                            position
+                        }
+                    }
+                    Bound::Unbounded => 0,
+                };
+                let last = match end {
+                    Bound::Included(k) => {
+                        let (position, found) = accessor.position::<K>(k);
+                        if found {
+                            position + 1
+                        } else {
+                            position
+                        }
+                    }
+                    Bound::Excluded(k) => accessor.position::<K>(k).0,
+                    Bound::Unbounded => accessor.num_pairs(),
+                };
+                Ok(last.saturating_sub(first).try_into().unwrap())
+            }
+            PageType::Branch => {
+                let accessor = BranchAccessor::new(&page, K::fixed_width());
+                let first = match start {
+                    Bound::Included(k) | Bound::Excluded(k) => accessor.child_for_key::<K>(k).0,
+                    Bound::Unbounded => 0,
+                };
+                let last = match end {
+                    Bound::Included(k) | Bound::Excluded(k) => accessor.child_for_key::<K>(k).0,
+                    Bound::Unbounded => accessor.count_children() - 1,
+                };
+                let mut count = 0;
+                for i in first..=last {
+                    let child_start = if i == first { start } else { Bound::Unbounded };
+                    let child_end = if i == last { end } else { Bound::Unbounded };
+                    count += self.count_range_helper(
+                        accessor.child_page(i).unwrap(),
+                        child_start,
+                        child_end,
+                    )?;
+                }
+                Ok(count)
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn tree_structure(&self) -> Result<Option<TreeNode>> {
+        if let Some((p, _)) = self.root {
+            Ok(Some(tree_node::<K, V>(p, self.mem)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Verifies that the keys are ordered, and that all leaves are at the same depth
+    pub(crate) fn verify_structure(&self) -> Result {
+        if let Some((p, _)) = self.root {
+            verify_structure_helper::<K>(p, None, None, V::fixed_width(), self.mem)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn stats(&self) -> Result<BtreeStats> {
+        btree_stats(
             self.root.map(|(p, _)| p),
             self.mem,
-        )?;
-        let mut count = 0;
-        for v in iter {
-            v?;
-            count += 1;
-        }
-        Ok(count)
+            K::fixed_width(),
+            V::fixed_width(),
+        )
     }
 
     #[allow(dead_code)]
@@ -621,15 +1629,14 @@ impl<'a, K: RedbKey, V: RedbValue> Btree<'a, K, V> {
             while !pages.is_empty() {
                 let mut next_children = vec![];
                 for page in pages.drain(..) {
-                    let node_mem = page.memory();
-                    match node_mem[0] {
-                        LEAF => {
+                    match check_page_type(&page)? {
+                        PageType::Leaf => {
                             eprint!("Leaf[ (page={:?})", page.get_page_number());
                             LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width())
                                 .print_node::<K, V>(include_values);
                             eprint!("]");
                         }
-                        BRANCH => {
+                        PageType::Branch => {
                             let accessor = BranchAccessor::new(&page, K::fixed_width());
                             for i in 0..accessor.count_children() {
                                 let child = accessor.child_page(i).unwrap();
@@ -637,7 +1644,6 @@ impl<'a, K: RedbKey, V: RedbValue> Btree<'a, K, V> {
                             }
                             accessor.print_node::<K>();
                         }
-                        _ => unreachable!(),
                     }
                     eprint!("  ");
                 }
@@ -667,6 +1673,7 @@ pub(crate) fn btree_stats(
             stored_leaf_bytes: 0,
             metadata_bytes: 0,
             fragmented_bytes: 0,
+            pages_per_level: vec![],
         })
     }
 }
@@ -678,9 +1685,8 @@ fn stats_helper(
     fixed_value_size: Option<usize>,
 ) -> Result<BtreeStats> {
     let page = mem.get_page(page_number)?;
-    let node_mem = page.memory();
-    match node_mem[0] {
-        LEAF => {
+    match check_page_type(&page)? {
+        PageType::Leaf => {
             let accessor = LeafAccessor::new(page.memory(), fixed_key_size, fixed_value_size);
             let leaf_bytes = accessor.length_of_pairs(0, accessor.num_pairs());
             let overhead_bytes = accessor.total_length() - leaf_bytes;
@@ -692,9 +1698,10 @@ fn stats_helper(
                 stored_leaf_bytes: leaf_bytes.try_into().unwrap(),
                 metadata_bytes: overhead_bytes.try_into().unwrap(),
                 fragmented_bytes,
+                pages_per_level: vec![1],
             })
         }
-        BRANCH => {
+        PageType::Branch => {
             let accessor = BranchAccessor::new(&page, fixed_key_size);
             let mut max_child_height = 0;
             let mut leaf_pages = 0;
@@ -702,15 +1709,27 @@ fn stats_helper(
             let mut stored_leaf_bytes = 0;
             let mut metadata_bytes = accessor.total_length() as u64;
             let mut fragmented_bytes = (page.memory().len() - accessor.total_length()) as u64;
-            for i in 0..accessor.count_children() {
-                if let Some(child) = accessor.child_page(i) {
-                    let stats = stats_helper(child, mem, fixed_key_size, fixed_value_size)?;
-                    max_child_height = max(max_child_height, stats.tree_height);
-                    leaf_pages += stats.leaf_pages;
-                    branch_pages += stats.branch_pages;
-                    stored_leaf_bytes += stats.stored_leaf_bytes;
-                    metadata_bytes += stats.metadata_bytes;
-                    fragmented_bytes += stats.fragmented_bytes;
+            let mut pages_per_level = vec![1];
+            let children: Vec<PageNumber> = (0..accessor.count_children())
+                .filter_map(|i| accessor.child_page(i))
+                .collect();
+            let child_stats = map_children(&children, |child| {
+                stats_helper(*child, mem, fixed_key_size, fixed_value_size)
+            })?;
+            for stats in child_stats {
+                max_child_height = max(max_child_height, stats.tree_height);
+                leaf_pages += stats.leaf_pages;
+                branch_pages += stats.branch_pages;
+                stored_leaf_bytes += stats.stored_leaf_bytes;
+                metadata_bytes += stats.metadata_bytes;
+                fragmented_bytes += stats.fragmented_bytes;
+                // Every child is at the same depth, so its levels line up with those below this page
+                for (depth, pages) in stats.pages_per_level.into_iter().enumerate() {
+                    if let Some(count) = pages_per_level.get_mut(depth + 1) {
+                        *count += pages;
+                    } else {
+                        pages_per_level.push(pages);
+                    }
                 }
             }
 
@@ -721,8 +1740,206 @@ fn stats_helper(
                 stored_leaf_bytes,
                 metadata_bytes,
                 fragmented_bytes,
+                pages_per_level,
+            })
+        }
+    }
+}
+
+/// A node of a table's btree, as returned by [`crate::ReadableTable::tree_structure`]
+///
+/// Pages are identified by their region, index, and order. Keys and values are formatted with
+/// their `Debug` implementations. When serialized, the variant is stored in a `type` field, as
+/// `"leaf"` or `"branch"`
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TreeNode {
+    Leaf {
+        page: (u32, u32, u8),
+        entries: Vec<(String, String)>,
+    },
+    Branch {
+        page: (u32, u32, u8),
+        keys: Vec<String>,
+        children: Vec<TreeNode>,
+    },
+}
+
+#[cfg(feature = "serde")]
+fn tree_node<K: RedbKey, V: RedbValue>(
+    page_number: PageNumber,
+    mem: &TransactionalMemory,
+) -> Result<TreeNode> {
+    let page = mem.get_page(page_number)?;
+    let page_id = (
+        page_number.region,
+        page_number.page_index,
+        page_number.page_order,
+    );
+    match check_page_type(&page)? {
+        PageType::Leaf => {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            let mut entries = vec![];
+            for i in 0..accessor.num_pairs() {
+                let entry = accessor.entry(i).unwrap();
+                entries.push((
+                    format!("{:?}", K::from_bytes(entry.key())),
+                    format!("{:?}", V::from_bytes(entry.value())),
+                ));
+            }
+            Ok(TreeNode::Leaf {
+                page: page_id,
+                entries,
             })
         }
-        _ => unreachable!(),
+        PageType::Branch => {
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            let mut keys = vec![];
+            let mut children = vec![];
+            for i in 0..accessor.count_children() {
+                if let Some(key) = accessor.key(i) {
+                    keys.push(format!("{:?}", K::from_bytes(key)));
+                }
+                children.push(tree_node::<K, V>(accessor.child_page(i).unwrap(), mem)?);
+            }
+            Ok(TreeNode::Branch {
+                page: page_id,
+                keys,
+                children,
+            })
+        }
+    }
+}
+
+// Returns the height of the subtree. All of its keys must be > lower and <= upper
+fn verify_structure_helper<K: RedbKey>(
+    page_number: PageNumber,
+    lower: Option<&[u8]>,
+    upper: Option<&[u8]>,
+    fixed_value_size: Option<usize>,
+    mem: &TransactionalMemory,
+) -> Result<usize> {
+    let page = mem.get_page(page_number)?;
+    match check_page_type(&page)? {
+        PageType::Leaf => {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), fixed_value_size);
+            let mut previous = lower;
+            for i in 0..accessor.num_pairs() {
+                let key = accessor.entry(i).unwrap().key();
+                if let Some(previous) = previous {
+                    if K::compare(previous, key).is_ge() {
+                        return Err(StorageError::Corrupted(format!(
+                            "Leaf page {:?} corrupted. Key {} is out of order",
+                            page_number, i
+                        )));
+                    }
+                }
+                previous = Some(key);
+            }
+            if let (Some(last), Some(upper)) = (previous, upper) {
+                if K::compare(last, upper).is_gt() {
+                    return Err(StorageError::Corrupted(format!(
+                        "Leaf page {:?} corrupted. Last key is greater than its separator",
+                        page_number
+                    )));
+                }
+            }
+            Ok(1)
+        }
+        PageType::Branch => {
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            let mut children = vec![];
+            for i in 0..accessor.count_children() {
+                let child_lower = if i == 0 { lower } else { accessor.key(i - 1) };
+                let child_upper = accessor.key(i).or(upper);
+                if let (Some(child_lower), Some(child_upper)) = (child_lower, child_upper) {
+                    if K::compare(child_lower, child_upper).is_ge() {
+                        return Err(StorageError::Corrupted(format!(
+                            "Branch page {:?} corrupted. Key {} is out of order",
+                            page_number, i
+                        )));
+                    }
+                }
+                children.push((accessor.child_page(i).unwrap(), child_lower, child_upper));
+            }
+            let heights = map_children(&children, |(child, child_lower, child_upper)| {
+                verify_structure_helper::<K>(
+                    *child,
+                    *child_lower,
+                    *child_upper,
+                    fixed_value_size,
+                    mem,
+                )
+            })?;
+            if heights.iter().any(|x| *x != heights[0]) {
+                return Err(StorageError::Corrupted(format!(
+                    "Branch page {:?} corrupted. Children have different heights",
+                    page_number
+                )));
+            }
+            Ok(heights[0] + 1)
+        }
+    }
+}
+
+// Applies `f` to each child. Sibling subtrees are processed in parallel, if the rayon feature is
+// enabled
+fn map_children<C: Sync, T: Send>(
+    children: &[C],
+    f: impl Fn(&C) -> Result<T> + Sync + Send,
+) -> Result<Vec<T>> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        children.par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        children.iter().map(f).collect()
+    }
+}
+
+fn encode_bound<'a, K: RedbKey + 'a, KR: Borrow<K::SelfType<'a>>>(
+    bound: Bound<&KR>,
+) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(k) => Bound::Included(K::as_bytes(k.borrow()).as_ref().to_vec()),
+        Bound::Excluded(k) => Bound::Excluded(K::as_bytes(k.borrow()).as_ref().to_vec()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn decode_bound<K: RedbKey>(bound: &Bound<Vec<u8>>) -> Bound<K::SelfType<'_>> {
+    match bound {
+        Bound::Included(x) => Bound::Included(K::from_bytes(x)),
+        Bound::Excluded(x) => Bound::Excluded(K::from_bytes(x)),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn as_slice_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(x) => Bound::Included(x.as_slice()),
+        Bound::Excluded(x) => Bound::Excluded(x.as_slice()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn last_key_in_subtree<K: RedbKey, V: RedbValue>(
+    page_number: PageNumber,
+    mem: &TransactionalMemory,
+) -> Result<Vec<u8>> {
+    let page = mem.get_page(page_number)?;
+    match check_page_type(&page)? {
+        PageType::Leaf => {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            Ok(accessor.last_entry().key().to_vec())
+        }
+        PageType::Branch => {
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            let last_child = accessor.child_page(accessor.count_children() - 1).unwrap();
+            last_key_in_subtree::<K, V>(last_child, mem)
+        }
     }
 }
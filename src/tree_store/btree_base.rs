@@ -17,40 +17,170 @@ pub(crate) type Checksum = u128;
 // Dummy value. Final value will be computed during commit
 pub(crate) const DEFERRED: Checksum = 999;
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(super) enum PageType {
+    Leaf,
+    Branch,
+}
+
+// Returns the type of a btree page, or an error if it is neither a leaf nor a branch, which means
+// that the page is corrupted or isn't part of a btree
+pub(super) fn check_page_type<T: Page>(page: &T) -> Result<PageType, StorageError> {
+    match page.memory()[0] {
+        LEAF => Ok(PageType::Leaf),
+        BRANCH => Ok(PageType::Branch),
+        page_type => Err(StorageError::Corrupted(format!(
+            "Page {:?} has invalid btree page type {}",
+            page.get_page_number(),
+            page_type
+        ))),
+    }
+}
+
 pub(super) fn leaf_checksum<T: Page>(
     page: &T,
     fixed_key_size: Option<usize>,
     fixed_value_size: Option<usize>,
 ) -> Result<Checksum, StorageError> {
-    let accessor = LeafAccessor::new(page.memory(), fixed_key_size, fixed_value_size);
-    let end = accessor.value_end(accessor.num_pairs() - 1).unwrap();
-    if end > page.memory().len() {
-        Err(StorageError::Corrupted(format!(
-            "Leaf page {:?} corrupted. Last offset {} beyond end of data {}",
-            page.get_page_number(),
-            end,
-            page.memory().len()
-        )))
-    } else {
-        Ok(xxh3_checksum(&page.memory()[..end]))
-    }
+    let end = check_leaf_offsets(page, fixed_key_size, fixed_value_size)?;
+    Ok(xxh3_checksum(&page.memory()[..end]))
 }
 
 pub(super) fn branch_checksum<T: Page>(
     page: &T,
     fixed_key_size: Option<usize>,
 ) -> Result<Checksum, StorageError> {
-    let accessor = BranchAccessor::new(page, fixed_key_size);
-    let end = accessor.key_end(accessor.num_keys() - 1);
-    if end > page.memory().len() {
-        Err(StorageError::Corrupted(format!(
-            "Branch page {:?} corrupted. Last offset {} beyond end of data {}",
+    let end = check_branch_offsets(page, fixed_key_size)?;
+    Ok(xxh3_checksum(&page.memory()[..end]))
+}
+
+// Checks that the header and every key and value offset of a leaf lie within the page, and that
+// the offsets are in order, so that its entries can be sliced without panicking. Returns the end
+// of the leaf's data
+fn check_leaf_offsets<T: Page>(
+    page: &T,
+    fixed_key_size: Option<usize>,
+    fixed_value_size: Option<usize>,
+) -> Result<usize, StorageError> {
+    let corrupted = |reason: String| {
+        StorageError::Corrupted(format!(
+            "Leaf page {:?} corrupted. {}",
+            page.get_page_number(),
+            reason
+        ))
+    };
+    let len = page.memory().len();
+    let accessor = LeafAccessor::new(page.memory(), fixed_key_size, fixed_value_size);
+    if accessor.num_pairs() == 0 {
+        return Err(corrupted("No entries".to_string()));
+    }
+    let mut previous = accessor.key_section_start();
+    if previous > len {
+        return Err(corrupted(format!(
+            "Header of {} entries beyond end of data {}",
+            accessor.num_pairs(),
+            len
+        )));
+    }
+    for i in 0..accessor.num_pairs() {
+        let end = accessor.key_end(i).unwrap();
+        if end < previous || end > len {
+            return Err(corrupted(format!("Invalid end offset {end} for key {i}")));
+        }
+        previous = end;
+    }
+    for i in 0..accessor.num_pairs() {
+        let end = accessor.value_end(i).unwrap();
+        if end < previous || end > len {
+            return Err(corrupted(format!("Invalid end offset {end} for value {i}")));
+        }
+        previous = end;
+    }
+
+    Ok(previous)
+}
+
+// Checks that the header and every key offset of a branch lie within the page, and that the
+// offsets are in order, so that its keys can be sliced without panicking. Returns the end of the
+// branch's data
+fn check_branch_offsets<T: Page>(
+    page: &T,
+    fixed_key_size: Option<usize>,
+) -> Result<usize, StorageError> {
+    let corrupted = |reason: String| {
+        StorageError::Corrupted(format!(
+            "Branch page {:?} corrupted. {}",
             page.get_page_number(),
-            end,
-            page.memory().len()
-        )))
-    } else {
-        Ok(xxh3_checksum(&page.memory()[..end]))
+            reason
+        ))
+    };
+    let len = page.memory().len();
+    let accessor = BranchAccessor::new(page, fixed_key_size);
+    if accessor.num_keys() == 0 {
+        return Err(corrupted("No keys".to_string()));
+    }
+    let mut previous = accessor.key_section_start();
+    if previous > len {
+        return Err(corrupted(format!(
+            "Header of {} keys beyond end of data {}",
+            accessor.num_keys(),
+            len
+        )));
+    }
+    for i in 0..accessor.num_keys() {
+        let end = accessor.key_end(i);
+        if end < previous || end > len {
+            return Err(corrupted(format!("Invalid end offset {end} for key {i}")));
+        }
+        previous = end;
+    }
+
+    Ok(previous)
+}
+
+// Compares a query against a key stored in the tree. In debug builds, also checks that
+// K::compare is antisymmetric, since an inconsistent implementation silently corrupts the tree
+fn compare_checked<K: RedbKey>(query: &[u8], key: &[u8]) -> Ordering {
+    let result = K::compare(query, key);
+    if cfg!(debug_assertions) {
+        let reversed = K::compare(key, query);
+        assert_eq!(
+            result,
+            reversed.reverse(),
+            "{}::compare is not antisymmetric: compare(a, b) returned {:?}, but compare(b, a) \
+             returned {:?}",
+            K::type_name().name(),
+            result,
+            reversed
+        );
+    }
+    result
+}
+
+// Checks, in debug builds, that the keys adjacent to the position found by a binary search are
+// ordered around the query. This fails if K::compare is not a total order, because the search
+// only compared the query against some of the keys in the page
+fn debug_check_neighbors<K: RedbKey>(lower: Option<&[u8]>, query: &[u8], upper: Option<&[u8]>) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    if let Some(lower) = lower {
+        assert_eq!(
+            K::compare(lower, query),
+            Ordering::Less,
+            "{}::compare is not a total order: binary search placed a key after a key which \
+             does not compare less than it",
+            K::type_name().name()
+        );
+    }
+    if let Some(upper) = upper {
+        assert_eq!(
+            K::compare(query, upper),
+            Ordering::Less,
+            "{}::compare is not a total order: binary search placed a key before a key which \
+             does not compare greater than it",
+            K::type_name().name()
+        );
     }
 }
 
@@ -155,9 +285,21 @@ impl<'a, V: RedbValue> AccessGuard<'a, V> {
         }
     }
 
+    // Copies the value into a new guard, and drops this one, which completes any deferred removal
+    #[cfg(feature = "paranoid")]
+    pub(super) fn into_owned(self) -> Self {
+        let value = self.page.memory()[self.offset..(self.offset + self.len)].to_vec();
+        Self::with_owned_value(value)
+    }
+
     pub fn value(&self) -> V::SelfType<'_> {
         V::from_bytes(&self.page.memory()[self.offset..(self.offset + self.len)])
     }
+
+    /// Like [`Self::value`], but returns an error instead of panicking if the stored data is invalid
+    pub fn try_value(&self) -> Result<V::SelfType<'_>, StorageError> {
+        V::try_from_bytes(&self.page.memory()[self.offset..(self.offset + self.len)])
+    }
 }
 
 impl<'a, V: RedbValue> Drop for AccessGuard<'a, V> {
@@ -202,6 +344,27 @@ impl<'a, V: RedbValue> AccessGuardMut<'a, V> {
             _value_type: Default::default(),
         }
     }
+
+    // Releases the page, and returns the page number, offset and length of the value
+    pub(super) fn into_location(self) -> (PageNumber, usize, usize) {
+        (self.page.get_page_number(), self.offset, self.len)
+    }
+}
+
+impl<'a, V: RedbValueMutInPlace<BaseRefType = [u8]>> AccessGuardMut<'a, V> {
+    /// Returns a mutable reference to the bytes in `range` of the value, without touching the rest of it
+    ///
+    /// Panics if `range` is out of bounds of the value
+    pub fn as_mut_range(&mut self, range: Range<usize>) -> &mut [u8] {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "range {range:?} is out of bounds for a value of length {}",
+            self.len
+        );
+        let start = self.offset + range.start;
+        let end = self.offset + range.end;
+        &mut self.page.memory_mut()[start..end]
+    }
 }
 
 impl<'a, V: RedbValueMutInPlace> AsMut<V::BaseRefType> for AccessGuardMut<'a, V> {
@@ -275,11 +438,16 @@ impl<'a> LeafAccessor<'a> {
         while min_entry < max_entry {
             let mid = (min_entry + max_entry) / 2;
             let key = self.key_unchecked(mid);
-            match K::compare(query, key) {
+            match compare_checked::<K>(query, key) {
                 Ordering::Less => {
                     max_entry = mid;
                 }
                 Ordering::Equal => {
+                    debug_check_neighbors::<K>(
+                        mid.checked_sub(1).map(|i| self.key_unchecked(i)),
+                        query,
+                        self.entry(mid + 1).map(|entry| entry.key()),
+                    );
                     return (mid, true);
                 }
                 Ordering::Greater => {
@@ -288,6 +456,11 @@ impl<'a> LeafAccessor<'a> {
             }
         }
         debug_assert_eq!(min_entry, max_entry);
+        debug_check_neighbors::<K>(
+            min_entry.checked_sub(1).map(|i| self.key_unchecked(i)),
+            query,
+            self.entry(min_entry).map(|entry| entry.key()),
+        );
         (min_entry, false)
     }
 
@@ -1080,11 +1253,16 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
         let mut max_child = self.num_keys(); // inclusive
         while min_child < max_child {
             let mid = (min_child + max_child) / 2;
-            match K::compare(query, self.key(mid).unwrap()) {
+            match compare_checked::<K>(query, self.key(mid).unwrap()) {
                 Ordering::Less => {
                     max_child = mid;
                 }
                 Ordering::Equal => {
+                    debug_check_neighbors::<K>(
+                        mid.checked_sub(1).and_then(|i| self.key(i)),
+                        query,
+                        self.key(mid + 1),
+                    );
                     return (mid, self.child_page(mid).unwrap());
                 }
                 Ordering::Greater => {
@@ -1093,6 +1271,11 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
             }
         }
         debug_assert_eq!(min_child, max_child);
+        debug_check_neighbors::<K>(
+            min_child.checked_sub(1).and_then(|i| self.key(i)),
+            query,
+            self.key(min_child),
+        );
 
         (min_child, self.child_page(min_child).unwrap())
     }
@@ -1,11 +1,11 @@
-use crate::tree_store::btree_base::{BranchAccessor, LeafAccessor};
-use crate::tree_store::btree_base::{BRANCH, LEAF};
+use crate::tree_store::btree_base::{check_page_type, BranchAccessor, LeafAccessor, PageType};
 use crate::tree_store::btree_iters::RangeIterState::{Internal, Leaf};
 use crate::tree_store::page_store::{Page, PageImpl, TransactionalMemory};
 use crate::tree_store::PageNumber;
 use crate::types::{RedbKey, RedbValue};
 use crate::Result;
 use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::collections::Bound;
 use std::marker::PhantomData;
 use std::ops::{Range, RangeBounds};
@@ -86,8 +86,8 @@ impl<'a> RangeIterState<'a> {
                         parent,
                     }));
                 }
-                match child_page.memory()[0] {
-                    LEAF => {
+                match check_page_type(&child_page)? {
+                    PageType::Leaf => {
                         let child_accessor = LeafAccessor::new(
                             child_page.memory(),
                             fixed_key_size,
@@ -106,7 +106,7 @@ impl<'a> RangeIterState<'a> {
                             parent,
                         }))
                     }
-                    BRANCH => {
+                    PageType::Branch => {
                         let child_accessor = BranchAccessor::new(&child_page, fixed_key_size);
                         let child = if reverse {
                             child_accessor.count_children() - 1
@@ -121,7 +121,6 @@ impl<'a> RangeIterState<'a> {
                             parent,
                         }))
                     }
-                    _ => unreachable!(),
                 }
             }
         }
@@ -177,6 +176,14 @@ impl<'a, K: RedbKey, V: RedbValue> EntryGuard<'a, K, V> {
         V::from_bytes(&self.page.memory()[self.value_range.clone()])
     }
 
+    pub(crate) fn raw_entry(&self) -> (&[u8], &[u8]) {
+        let memory = self.page.memory();
+        (
+            &memory[self.key_range.clone()],
+            &memory[self.value_range.clone()],
+        )
+    }
+
     pub(crate) fn into_raw(self) -> (PageImpl<'a>, Range<usize>, Range<usize>) {
         (self.page, self.key_range, self.value_range)
     }
@@ -195,23 +202,21 @@ impl<'a> AllPageNumbersBtreeIter<'a> {
         manager: &'a TransactionalMemory,
     ) -> Result<Self> {
         let root_page = manager.get_page(root)?;
-        let node_mem = root_page.memory();
-        let start = match node_mem[0] {
-            LEAF => Leaf {
+        let start = match check_page_type(&root_page)? {
+            PageType::Leaf => Leaf {
                 page: root_page,
                 fixed_key_size,
                 fixed_value_size,
                 entry: 0,
                 parent: None,
             },
-            BRANCH => Internal {
+            PageType::Branch => Internal {
                 page: root_page,
                 fixed_key_size,
                 fixed_value_size,
                 child: 0,
                 parent: None,
             },
-            _ => unreachable!(),
         };
         Ok(Self {
             next: Some(start),
@@ -406,6 +411,7 @@ pub(crate) struct BtreeRangeIter<'a, K: RedbKey + 'a, V: RedbValue + 'a> {
     right: Option<RangeIterState<'a>>, // Exclusive. The previous element returned
     include_left: bool,               // left is inclusive, instead of exclusive
     include_right: bool,              // right is inclusive, instead of exclusive
+    root: Option<PageNumber>,
     manager: &'a TransactionalMemory,
     _key_type: PhantomData<K>,
     _value_type: PhantomData<V>,
@@ -468,6 +474,7 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeRangeIter<'a, K, V> {
                 right,
                 include_left,
                 include_right,
+                root: Some(root),
                 manager,
                 _key_type: Default::default(),
                 _value_type: Default::default(),
@@ -478,6 +485,7 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeRangeIter<'a, K, V> {
                 right: None,
                 include_left: false,
                 include_right: false,
+                root: None,
                 manager,
                 _key_type: Default::default(),
                 _value_type: Default::default(),
@@ -486,6 +494,78 @@ impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeRangeIter<'a, K, V> {
     }
 }
 
+impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> BtreeRangeIter<'a, K, V> {
+    // Moves the left side of the iterator forward to the first entry >= key. This never moves
+    // the iterator backwards or past the right side of the range
+    pub(crate) fn seek(&mut self, key: &[u8]) -> Result {
+        if let Some(entry) = self.left.as_ref().and_then(|x| x.get_entry::<K, V>()) {
+            if K::compare(key, &entry.key_data()).is_le() {
+                return Ok(());
+            }
+        }
+        let past_end = match self.right.as_ref().and_then(|x| x.get_entry::<K, V>()) {
+            Some(entry) => match K::compare(key, &entry.key_data()) {
+                Ordering::Less => false,
+                Ordering::Equal => !self.include_right,
+                Ordering::Greater => true,
+            },
+            None => true,
+        };
+        if past_end || self.left.is_none() {
+            self.left = None;
+            self.right = None;
+            return Ok(());
+        }
+
+        let (include_left, left) = find_iter_left::<K, V>(
+            self.manager.get_page(self.root.unwrap())?,
+            None,
+            key,
+            true,
+            self.manager,
+        )?;
+        self.left = left;
+        self.include_left = include_left;
+
+        Ok(())
+    }
+
+    // Moves the right side of the iterator backward to the last entry <= key. This never moves
+    // the iterator forwards or past the left side of the range
+    pub(crate) fn seek_back(&mut self, key: &[u8]) -> Result {
+        if let Some(entry) = self.right.as_ref().and_then(|x| x.get_entry::<K, V>()) {
+            if K::compare(key, &entry.key_data()).is_ge() {
+                return Ok(());
+            }
+        }
+        let past_start = match self.left.as_ref().and_then(|x| x.get_entry::<K, V>()) {
+            Some(entry) => match K::compare(key, &entry.key_data()) {
+                Ordering::Less => true,
+                Ordering::Equal => !self.include_left,
+                Ordering::Greater => false,
+            },
+            None => true,
+        };
+        if past_start || self.right.is_none() {
+            self.left = None;
+            self.right = None;
+            return Ok(());
+        }
+
+        let (include_right, right) = find_iter_right::<K, V>(
+            self.manager.get_page(self.root.unwrap())?,
+            None,
+            key,
+            true,
+            self.manager,
+        )?;
+        self.right = right;
+        self.include_right = include_right;
+
+        Ok(())
+    }
+}
+
 impl<'a, K: RedbKey + 'a, V: RedbValue + 'a> Iterator for BtreeRangeIter<'a, K, V> {
     type Item = Result<EntryGuard<'a, K, V>>;
 
@@ -626,9 +706,8 @@ fn find_iter_unbounded<'a, K: RedbKey, V: RedbValue>(
     reverse: bool,
     manager: &'a TransactionalMemory,
 ) -> Result<Option<RangeIterState<'a>>> {
-    let node_mem = page.memory();
-    match node_mem[0] {
-        LEAF => {
+    match check_page_type(&page)? {
+        PageType::Leaf => {
             let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
             let entry = if reverse { accessor.num_pairs() - 1 } else { 0 };
             Ok(Some(Leaf {
@@ -639,7 +718,7 @@ fn find_iter_unbounded<'a, K: RedbKey, V: RedbValue>(
                 parent,
             }))
         }
-        BRANCH => {
+        PageType::Branch => {
             let accessor = BranchAccessor::new(&page, K::fixed_width());
             let child_index = if reverse {
                 accessor.count_children() - 1
@@ -660,7 +739,6 @@ fn find_iter_unbounded<'a, K: RedbKey, V: RedbValue>(
             }));
             find_iter_unbounded::<K, V>(child_page, parent, reverse, manager)
         }
-        _ => unreachable!(),
     }
 }
 
@@ -673,9 +751,8 @@ fn find_iter_left<'a, K: RedbKey, V: RedbValue>(
     include_query: bool,
     manager: &'a TransactionalMemory,
 ) -> Result<(bool, Option<RangeIterState<'a>>)> {
-    let node_mem = page.memory();
-    match node_mem[0] {
-        LEAF => {
+    match check_page_type(&page)? {
+        PageType::Leaf => {
             let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
             let (mut position, found) = accessor.position::<K>(query);
             let include = if position < accessor.num_pairs() {
@@ -695,7 +772,7 @@ fn find_iter_left<'a, K: RedbKey, V: RedbValue>(
             };
             Ok((include, Some(result)))
         }
-        BRANCH => {
+        PageType::Branch => {
             let accessor = BranchAccessor::new(&page, K::fixed_width());
             let (child_index, child_page_number) = accessor.child_for_key::<K>(query);
             let child_page = manager.get_page(child_page_number)?;
@@ -710,7 +787,6 @@ fn find_iter_left<'a, K: RedbKey, V: RedbValue>(
             }
             find_iter_left::<K, V>(child_page, parent, query, include_query, manager)
         }
-        _ => unreachable!(),
     }
 }
 
@@ -721,9 +797,8 @@ fn find_iter_right<'a, K: RedbKey, V: RedbValue>(
     include_query: bool,
     manager: &'a TransactionalMemory,
 ) -> Result<(bool, Option<RangeIterState<'a>>)> {
-    let node_mem = page.memory();
-    match node_mem[0] {
-        LEAF => {
+    match check_page_type(&page)? {
+        PageType::Leaf => {
             let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
             let (mut position, found) = accessor.position::<K>(query);
             let include = if position < accessor.num_pairs() {
@@ -743,7 +818,7 @@ fn find_iter_right<'a, K: RedbKey, V: RedbValue>(
             };
             Ok((include, Some(result)))
         }
-        BRANCH => {
+        PageType::Branch => {
             let accessor = BranchAccessor::new(&page, K::fixed_width());
             let (child_index, child_page_number) = accessor.child_for_key::<K>(query);
             let child_page = manager.get_page(child_page_number)?;
@@ -758,6 +833,5 @@ fn find_iter_right<'a, K: RedbKey, V: RedbValue>(
             }
             find_iter_right::<K, V>(child_page, parent, query, include_query, manager)
         }
-        _ => unreachable!(),
     }
 }
@@ -1,6 +1,6 @@
 use crate::tree_store::btree_base::{
-    BranchAccessor, BranchBuilder, BranchMutator, Checksum, LeafAccessor, LeafBuilder, LeafMutator,
-    BRANCH, DEFERRED, LEAF,
+    check_page_type, BranchAccessor, BranchBuilder, BranchMutator, Checksum, LeafAccessor,
+    LeafBuilder, LeafMutator, PageType, RawBranchBuilder, DEFERRED,
 };
 use crate::tree_store::btree_mutator::DeletionResult::{
     DeletedBranch, DeletedLeaf, PartialBranch, PartialLeaf, Subtree,
@@ -11,6 +11,7 @@ use crate::types::{RedbKey, RedbValue};
 use crate::{AccessGuard, Result};
 use std::cmp::{max, min};
 use std::marker::PhantomData;
+use std::ops::Bound;
 
 // TODO: it seems like Checksum can be removed from most/all of these, now that we're using deferred checksums
 #[derive(Debug)]
@@ -34,12 +35,38 @@ struct InsertionResult<'a, V: RedbValue> {
     root_checksum: Checksum,
     // Following sibling, if the root had to be split
     additional_sibling: Option<(Vec<u8>, PageNumber, Checksum)>,
-    // The inserted value for .insert_reserve() to use
-    inserted_value: AccessGuardMut<'a, V>,
+    // The inserted value for .insert_reserve() to use. None if no guard was requested, or if the key
+    // was already present and the insertion was only_if_absent, in which case the subtree is
+    // unchanged
+    inserted_value: Option<AccessGuardMut<'a, V>>,
     // The previous value, if any
     old_value: Option<AccessGuard<'a, V>>,
 }
 
+// The value of an insertion. A deferred value is only computed once the insertion reaches the leaf
+// for its key, and only if it will be inserted
+enum InsertValue<'v> {
+    Bytes(&'v [u8]),
+    Deferred(&'v mut dyn FnMut() -> Result<Vec<u8>>),
+}
+
+// The parts of a tree which remain after the entries in a range are removed from it
+struct RangeDeletion {
+    // Subtrees to the left and right of the range, indexed by depth, which are kept as is. Each
+    // is stored with a key that is greater than or equal to every key in it
+    left: Vec<Vec<(PageNumber, Checksum, Vec<u8>)>>,
+    right: Vec<Vec<(PageNumber, Checksum, Vec<u8>)>>,
+    // Entries of the leaves at the ends of the range, which are outside of it
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    leaf_depth: usize,
+    // Pages on the paths to the ends of the range, which are rebuilt
+    rewritten: Vec<PageNumber>,
+    // Subtrees which are entirely within the range
+    deleted: Vec<PageNumber>,
+    // Number of entries removed from the leaves at the ends of the range
+    removed: u64,
+}
+
 pub(crate) struct MutateHelper<'a, 'b, K: RedbKey, V: RedbValue> {
     root: &'b mut Option<(PageNumber, Checksum)>,
     modify_uncommitted: bool,
@@ -123,19 +150,359 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
         }
     }
 
+    // Removes every entry within the bounds in a single pass, and returns the number removed.
+    // Subtrees which are entirely within the bounds are freed without being rewritten, and only
+    // the pages on the paths to the two ends of the range are rebuilt
+    pub(crate) fn delete_range(&mut self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<u64> {
+        let root = if let Some((p, _)) = *self.root {
+            p
+        } else {
+            return Ok(0);
+        };
+        let mut state = RangeDeletion {
+            left: vec![vec![]],
+            right: vec![vec![]],
+            entries: vec![],
+            leaf_depth: 0,
+            rewritten: vec![],
+            deleted: vec![],
+            removed: 0,
+        };
+        self.collect_range_deletion(root, 0, start, end, None, &mut state)?;
+        if state.removed == 0 && state.deleted.is_empty() {
+            return Ok(0);
+        }
+
+        // Everything needed from the rewritten pages has been copied out, so they can be freed
+        let mut removed = state.removed;
+        for page_number in std::mem::take(&mut state.deleted) {
+            removed += self.free_subtree(page_number)?;
+        }
+        for page_number in std::mem::take(&mut state.rewritten) {
+            self.conditional_free(page_number);
+        }
+
+        // Merge the remaining entries with a neighbouring leaf, if they would fill less than a
+        // third of a page, the same as a deletion which leaves a partial leaf
+        let leaf_depth = state.leaf_depth;
+        let entry_bytes = state.entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+        if leaf_depth > 0
+            && !state.entries.is_empty()
+            && LeafBuilder::required_bytes(state.entries.len(), entry_bytes)
+                < self.mem.get_page_size() / 3
+        {
+            if let Some((page_number, _, _)) = state.left[leaf_depth].pop() {
+                let mut entries = self.take_leaf_entries(page_number)?;
+                entries.append(&mut state.entries);
+                state.entries = entries;
+            } else if !state.right[leaf_depth].is_empty() {
+                let (page_number, _, _) = state.right[leaf_depth].remove(0);
+                let mut entries = self.take_leaf_entries(page_number)?;
+                state.entries.append(&mut entries);
+            }
+        }
+
+        // Rebuild the paths bottom up, between the subtrees which are kept on either side. The new
+        // pages aren't freed if this fails, since the old ones already have been
+        let built = &mut vec![];
+        let mut level = self.build_leaves(
+            std::mem::take(&mut state.entries).into_iter().map(Ok),
+            built,
+        )?;
+        for depth in (0..leaf_depth).rev() {
+            let children = state.left[depth + 1].len() + level.len() + state.right[depth + 1].len();
+            if children < 2 {
+                // A branch needs at least two children. Borrow them from the nearest subtree
+                // that is kept, by replacing it with its children at each level down to this one
+                let nearest = (1..=depth)
+                    .rev()
+                    .find(|&d| !state.left[d].is_empty() || !state.right[d].is_empty());
+                if let Some(nearest) = nearest {
+                    for d in nearest..=depth {
+                        self.unfold_kept_subtree(&mut state, d)?;
+                    }
+                }
+            }
+            let mut children = std::mem::take(&mut state.left[depth + 1]);
+            children.append(&mut level);
+            children.append(&mut state.right[depth + 1]);
+            level = if children.len() < 2 {
+                // Nothing is kept above this level, so the remaining page, if any, is the root
+                children
+            } else {
+                self.build_branches(children, built)?
+            };
+        }
+        while level.len() > 1 {
+            level = self.build_branches(level, built)?;
+        }
+        *self.root = level
+            .pop()
+            .map(|(page_number, checksum, _)| (page_number, checksum));
+
+        Ok(removed)
+    }
+
+    // Walks the paths to the two ends of the range, and records what remains of the subtree
+    // rooted at page_number after the entries within the bounds are removed. upper is the key in
+    // the parent which bounds this subtree, if any
+    fn collect_range_deletion(
+        &self,
+        page_number: PageNumber,
+        depth: usize,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        upper: Option<&[u8]>,
+        state: &mut RangeDeletion,
+    ) -> Result {
+        let page = self.mem.get_page(page_number)?;
+        state.rewritten.push(page_number);
+        if check_page_type(&page)? == PageType::Leaf {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            let first = match start {
+                Bound::Included(k) => accessor.position::<K>(k).0,
+                Bound::Excluded(k) => {
+                    let (position, found) = accessor.position::<K>(k);
+                    if found {
+                        position + 1
+                    } else {
+                        position
+                    }
+                }
+                Bound::Unbounded => 0,
+            };
+            let last = match end {
+                Bound::Included(k) => {
+                    let (position, found) = accessor.position::<K>(k);
+                    if found {
+                        position + 1
+                    } else {
+                        position
+                    }
+                }
+                Bound::Excluded(k) => accessor.position::<K>(k).0,
+                Bound::Unbounded => accessor.num_pairs(),
+            };
+            let last = max(first, last);
+            for i in (0..first).chain(last..accessor.num_pairs()) {
+                let entry = accessor.entry(i).unwrap();
+                state
+                    .entries
+                    .push((entry.key().to_vec(), entry.value().to_vec()));
+            }
+            state.removed += u64::try_from(last - first).unwrap();
+            state.leaf_depth = depth;
+        } else {
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            let first = match start {
+                Bound::Included(k) | Bound::Excluded(k) => accessor.child_for_key::<K>(k).0,
+                Bound::Unbounded => 0,
+            };
+            let last = match end {
+                Bound::Included(k) | Bound::Excluded(k) => accessor.child_for_key::<K>(k).0,
+                Bound::Unbounded => accessor.count_children() - 1,
+            };
+            if state.left.len() < depth + 2 {
+                state.left.push(vec![]);
+                state.right.push(vec![]);
+            }
+            for i in 0..accessor.count_children() {
+                let child = accessor.child_page(i).unwrap();
+                let checksum = accessor.child_checksum(i).unwrap();
+                let child_upper = accessor.key(i).or(upper);
+                if i < first {
+                    let key = child_upper.unwrap().to_vec();
+                    state.left[depth + 1].push((child, checksum, key));
+                } else if i > last {
+                    // Only the rightmost subtree of the tree has no bound, and it never needs a
+                    // separator key, since nothing follows it
+                    let key = child_upper.unwrap_or_default().to_vec();
+                    state.right[depth + 1].push((child, checksum, key));
+                } else {
+                    let child_start = if i == first { start } else { Bound::Unbounded };
+                    let child_end = if i == last { end } else { Bound::Unbounded };
+                    if matches!(child_start, Bound::Unbounded)
+                        && matches!(child_end, Bound::Unbounded)
+                    {
+                        state.deleted.push(child);
+                    } else {
+                        self.collect_range_deletion(
+                            child,
+                            depth + 1,
+                            child_start,
+                            child_end,
+                            child_upper,
+                            state,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Replaces the kept subtree nearest to the range at the given depth with its children, which
+    // are one level deeper
+    fn unfold_kept_subtree(&mut self, state: &mut RangeDeletion, depth: usize) -> Result {
+        if let Some((page_number, _, key)) = state.left[depth].pop() {
+            let mut children = self.take_branch_children(page_number, key)?;
+            children.append(&mut state.left[depth + 1]);
+            state.left[depth + 1] = children;
+        } else {
+            let (page_number, _, key) = state.right[depth].remove(0);
+            let mut children = self.take_branch_children(page_number, key)?;
+            state.right[depth + 1].append(&mut children);
+        }
+
+        Ok(())
+    }
+
+    // Frees the branch page, and returns its children. key bounds the last child
+    fn take_branch_children(
+        &mut self,
+        page_number: PageNumber,
+        key: Vec<u8>,
+    ) -> Result<Vec<(PageNumber, Checksum, Vec<u8>)>> {
+        let page = self.mem.get_page(page_number)?;
+        let accessor = BranchAccessor::new(&page, K::fixed_width());
+        let mut children = vec![];
+        for i in 0..accessor.count_children() {
+            let child_key = accessor.key(i).map_or_else(|| key.clone(), |x| x.to_vec());
+            children.push((
+                accessor.child_page(i).unwrap(),
+                accessor.child_checksum(i).unwrap(),
+                child_key,
+            ));
+        }
+        drop(page);
+        self.conditional_free(page_number);
+
+        Ok(children)
+    }
+
+    // Frees the leaf page, and returns its entries
+    fn take_leaf_entries(&mut self, page_number: PageNumber) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let page = self.mem.get_page(page_number)?;
+        let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+        let mut entries = vec![];
+        for i in 0..accessor.num_pairs() {
+            let entry = accessor.entry(i).unwrap();
+            entries.push((entry.key().to_vec(), entry.value().to_vec()));
+        }
+        drop(page);
+        self.conditional_free(page_number);
+
+        Ok(entries)
+    }
+
+    // Frees every page of the subtree, and returns the number of entries it contained
+    fn free_subtree(&mut self, page_number: PageNumber) -> Result<u64> {
+        let page = self.mem.get_page(page_number)?;
+        let mut removed = 0;
+        let mut children = vec![];
+        if check_page_type(&page)? == PageType::Leaf {
+            let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+            removed += u64::try_from(accessor.num_pairs()).unwrap();
+        } else {
+            let accessor = BranchAccessor::new(&page, K::fixed_width());
+            for i in 0..accessor.count_children() {
+                children.push(accessor.child_page(i).unwrap());
+            }
+        }
+        drop(page);
+        self.conditional_free(page_number);
+        for child in children {
+            removed += self.free_subtree(child)?;
+        }
+
+        Ok(removed)
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn insert(
         &mut self,
         key: &K::SelfType<'_>,
         value: &V::SelfType<'_>,
     ) -> Result<(Option<AccessGuard<'a, V>>, AccessGuardMut<'a, V>)> {
+        self.insert_raw(K::as_bytes(key).as_ref(), V::as_bytes(value).as_ref())
+    }
+
+    // Inserts an already encoded key and value
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn insert_raw(
+        &mut self,
+        key_bytes: &[u8],
+        value_bytes: &[u8],
+    ) -> Result<(Option<AccessGuard<'a, V>>, AccessGuardMut<'a, V>)> {
+        let (old_value, guard) =
+            self.insert_raw_helper(key_bytes, &mut InsertValue::Bytes(value_bytes), false, true)?;
+        Ok((old_value, guard.unwrap()))
+    }
+
+    // Inserts the key and value without building a guard for the inserted value, which saves
+    // fetching its page mutably again after the insertion. Returns the previous value, if any
+    pub(crate) fn insert_without_guard(
+        &mut self,
+        key: &K::SelfType<'_>,
+        value: &V::SelfType<'_>,
+    ) -> Result<Option<AccessGuard<'a, V>>> {
+        let (old_value, _) = self.insert_raw_helper(
+            K::as_bytes(key).as_ref(),
+            &mut InsertValue::Bytes(V::as_bytes(value).as_ref()),
+            false,
+            false,
+        )?;
+        Ok(old_value)
+    }
+
+    // Inserts the key and value, unless the key is already present, in which case the tree is left
+    // unchanged. Returns true if the value was inserted
+    pub(crate) fn insert_if_absent(
+        &mut self,
+        key: &K::SelfType<'_>,
+        value: &V::SelfType<'_>,
+    ) -> Result<bool> {
+        let (old_value, _) = self.insert_raw_helper(
+            K::as_bytes(key).as_ref(),
+            &mut InsertValue::Bytes(V::as_bytes(value).as_ref()),
+            true,
+            false,
+        )?;
+        Ok(old_value.is_none())
+    }
+
+    // Like insert_if_absent(), but the value is computed by f, which is only called if the key is
+    // absent. Returns either the existing value, or a guard for the inserted one
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn insert_if_absent_with(
+        &mut self,
+        key_bytes: &[u8],
+        f: &mut dyn FnMut() -> Result<Vec<u8>>,
+    ) -> Result<(Option<AccessGuard<'a, V>>, Option<AccessGuardMut<'a, V>>)> {
+        self.insert_raw_helper(key_bytes, &mut InsertValue::Deferred(f), true, true)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn insert_raw_helper(
+        &mut self,
+        key_bytes: &[u8],
+        value: &mut InsertValue<'_>,
+        only_if_absent: bool,
+        with_guard: bool,
+    ) -> Result<(Option<AccessGuard<'a, V>>, Option<AccessGuardMut<'a, V>>)> {
         let (new_root, old_value, guard) = if let Some((p, checksum)) = *self.root {
             let result = self.insert_helper(
                 self.mem.get_page(p)?,
                 checksum,
-                K::as_bytes(key).as_ref(),
-                V::as_bytes(value).as_ref(),
+                key_bytes,
+                value,
+                only_if_absent,
+                with_guard,
             )?;
+            if only_if_absent && result.old_value.is_some() {
+                return Ok((result.old_value, None));
+            }
 
             let new_root = if let Some((key, page2, page2_checksum)) = result.additional_sibling {
                 let mut builder = BranchBuilder::new(self.mem, 2, K::fixed_width());
@@ -149,10 +516,14 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
             };
             (new_root, result.old_value, result.inserted_value)
         } else {
-            let key_bytes = K::as_bytes(key);
-            let value_bytes = V::as_bytes(value);
-            let key_bytes = key_bytes.as_ref();
-            let value_bytes = value_bytes.as_ref();
+            let computed;
+            let value_bytes = match value {
+                InsertValue::Bytes(bytes) => *bytes,
+                InsertValue::Deferred(f) => {
+                    computed = f()?;
+                    computed.as_slice()
+                }
+            };
             let mut builder = LeafBuilder::new(self.mem, 1, K::fixed_width(), V::fixed_width());
             builder.push(key_bytes, value_bytes);
             let page = builder.build()?;
@@ -160,7 +531,7 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
             let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
             let offset = accessor.offset_of_first_value();
             let page_num = page.get_page_number();
-            let guard = AccessGuardMut::new(page, offset, value_bytes.len());
+            let guard = with_guard.then(|| AccessGuardMut::new(page, offset, value_bytes.len()));
 
             ((page_num, DEFERRED), None, guard)
         };
@@ -168,19 +539,230 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
         Ok((old_value, guard))
     }
 
+    // Appends the entries, which must be sorted and greater than every key in the tree. Only the
+    // rightmost path of the tree is rewritten, and the new pages are filled, rather than split
+    // in half as they would be by individual inserts
+    pub(crate) fn append_sorted(
+        &mut self,
+        entries: impl Iterator<Item = Result<(Vec<u8>, Vec<u8>)>>,
+    ) -> Result {
+        let mut entries = entries.peekable();
+        if entries.peek().is_none() {
+            return Ok(());
+        }
+
+        // Copy out the rightmost path, since it's replaced by the new pages. Each branch keeps all
+        // its children except the rightmost, along with their separator keys
+        let mut right_path = vec![];
+        let mut right_branches: Vec<Vec<(PageNumber, Checksum, Vec<u8>)>> = vec![];
+        let mut right_leaf = vec![];
+        let mut next_page = self.root.map(|(p, _)| p);
+        while let Some(page_number) = next_page {
+            let page = self.mem.get_page(page_number)?;
+            match check_page_type(&page)? {
+                PageType::Leaf => {
+                    let accessor =
+                        LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                    for i in 0..accessor.num_pairs() {
+                        let entry = accessor.entry(i).unwrap();
+                        right_leaf.push((entry.key().to_vec(), entry.value().to_vec()));
+                    }
+                    next_page = None;
+                }
+                PageType::Branch => {
+                    let accessor = BranchAccessor::new(&page, K::fixed_width());
+                    let last = accessor.count_children() - 1;
+                    let mut children = vec![];
+                    for i in 0..last {
+                        children.push((
+                            accessor.child_page(i).unwrap(),
+                            accessor.child_checksum(i).unwrap(),
+                            accessor.key(i).unwrap().to_vec(),
+                        ));
+                    }
+                    right_branches.push(children);
+                    next_page = Some(accessor.child_page(last).unwrap());
+                }
+            }
+            right_path.push(page_number);
+        }
+
+        // The tree is left unchanged if building fails, so the pages built so far are freed, and
+        // the rightmost path is only freed once it has been replaced
+        let mut built = vec![];
+        match self.build_sorted(right_leaf, right_branches, entries, &mut built) {
+            Ok(root) => {
+                *self.root = Some(root);
+                for page_number in right_path {
+                    self.conditional_free(page_number);
+                }
+                Ok(())
+            }
+            Err(err) => {
+                for page_number in built {
+                    self.mem.free(page_number);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn build_sorted(
+        &mut self,
+        right_leaf: Vec<(Vec<u8>, Vec<u8>)>,
+        mut right_branches: Vec<Vec<(PageNumber, Checksum, Vec<u8>)>>,
+        entries: impl Iterator<Item = Result<(Vec<u8>, Vec<u8>)>>,
+        built: &mut Vec<PageNumber>,
+    ) -> Result<(PageNumber, Checksum)> {
+        // Each level is a list of pages, along with the greatest key in each
+        let mut level = self.build_leaves(right_leaf.into_iter().map(Ok).chain(entries), built)?;
+        while let Some(mut children) = right_branches.pop() {
+            children.append(&mut level);
+            level = self.build_branches(children, built)?;
+        }
+        while level.len() > 1 {
+            level = self.build_branches(level, built)?;
+        }
+        let (root, checksum, _) = level.pop().unwrap();
+
+        Ok((root, checksum))
+    }
+
+    fn build_leaves(
+        &mut self,
+        entries: impl Iterator<Item = Result<(Vec<u8>, Vec<u8>)>>,
+        built: &mut Vec<PageNumber>,
+    ) -> Result<Vec<(PageNumber, Checksum, Vec<u8>)>> {
+        let mut result = vec![];
+        let mut pending: Vec<(Vec<u8>, Vec<u8>)> = vec![];
+        let mut pending_bytes = 0;
+        for entry in entries {
+            let (key, value) = entry?;
+            let entry_bytes = key.len() + value.len();
+            if !pending.is_empty()
+                && LeafBuilder::required_bytes(pending.len() + 1, pending_bytes + entry_bytes)
+                    > self.mem.get_page_size()
+            {
+                result.push(self.build_leaf(&pending, built)?);
+                pending.clear();
+                pending_bytes = 0;
+            }
+            pending.push((key, value));
+            pending_bytes += entry_bytes;
+        }
+        if !pending.is_empty() {
+            result.push(self.build_leaf(&pending, built)?);
+        }
+
+        Ok(result)
+    }
+
+    fn build_leaf(
+        &mut self,
+        entries: &[(Vec<u8>, Vec<u8>)],
+        built: &mut Vec<PageNumber>,
+    ) -> Result<(PageNumber, Checksum, Vec<u8>)> {
+        let mut builder =
+            LeafBuilder::new(self.mem, entries.len(), K::fixed_width(), V::fixed_width());
+        for (key, value) in entries {
+            builder.push(key, value);
+        }
+        let page = builder.build()?;
+        built.push(page.get_page_number());
+        let (last_key, _) = entries.last().unwrap();
+
+        Ok((page.get_page_number(), DEFERRED, last_key.clone()))
+    }
+
+    // Packs the children into as few branch pages as possible
+    fn build_branches(
+        &mut self,
+        children: Vec<(PageNumber, Checksum, Vec<u8>)>,
+        built: &mut Vec<PageNumber>,
+    ) -> Result<Vec<(PageNumber, Checksum, Vec<u8>)>> {
+        let mut groups: Vec<Vec<(PageNumber, Checksum, Vec<u8>)>> = vec![vec![]];
+        let mut group_key_bytes = 0;
+        for child in children {
+            let group = groups.last().unwrap();
+            // Adding another child makes the key of the current last child a separator. Require
+            // more than three children before starting a new group, so that one can be moved to
+            // the final group below
+            if group.len() > 3
+                && RawBranchBuilder::required_bytes(group.len(), group_key_bytes, K::fixed_width())
+                    > self.mem.get_page_size()
+            {
+                groups.push(vec![]);
+                group_key_bytes = 0;
+            }
+            group_key_bytes += child.2.len();
+            groups.last_mut().unwrap().push(child);
+        }
+        // Every branch needs at least two children
+        if groups.len() > 1 && groups.last().unwrap().len() == 1 {
+            let previous = groups.len() - 2;
+            let moved = groups[previous].pop().unwrap();
+            groups.last_mut().unwrap().insert(0, moved);
+        }
+
+        let mut result = vec![];
+        for group in groups {
+            debug_assert!(group.len() > 1);
+            let mut builder = BranchBuilder::new(self.mem, group.len(), K::fixed_width());
+            for (i, (page_number, checksum, key)) in group.iter().enumerate() {
+                builder.push_child(*page_number, *checksum);
+                if i < group.len() - 1 {
+                    builder.push_key(key);
+                }
+            }
+            let page = builder.build()?;
+            built.push(page.get_page_number());
+            let (_, _, last_key) = group.last().unwrap();
+            result.push((page.get_page_number(), DEFERRED, last_key.clone()));
+        }
+
+        Ok(result)
+    }
+
     fn insert_helper(
         &mut self,
         page: PageImpl<'a>,
         page_checksum: Checksum,
         key: &[u8],
-        value: &[u8],
+        value: &mut InsertValue<'_>,
+        only_if_absent: bool,
+        with_guard: bool,
     ) -> Result<InsertionResult<'a, V>> {
-        let node_mem = page.memory();
-        Ok(match node_mem[0] {
-            LEAF => {
+        Ok(match check_page_type(&page)? {
+            PageType::Leaf => {
                 let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
                 let (position, found) = accessor.position::<K>(key);
 
+                if found && only_if_absent {
+                    let (start, end) = accessor.value_range(position).unwrap();
+                    return Ok(InsertionResult {
+                        new_root: page.get_page_number(),
+                        root_checksum: page_checksum,
+                        additional_sibling: None,
+                        inserted_value: None,
+                        old_value: Some(AccessGuard::new(
+                            page,
+                            start,
+                            end - start,
+                            false,
+                            self.mem,
+                        )),
+                    });
+                }
+
+                let computed;
+                let value = match value {
+                    InsertValue::Bytes(bytes) => *bytes,
+                    InsertValue::Deferred(f) => {
+                        computed = f()?;
+                        computed.as_slice()
+                    }
+                };
+
                 // Fast-path to avoid re-building and splitting pages with a single large value
                 let single_large_value = accessor.num_pairs() == 1
                     && accessor.total_length() >= self.mem.get_page_size();
@@ -194,7 +776,8 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
                         LeafAccessor::new(new_page.memory(), K::fixed_width(), V::fixed_width());
                     let offset = new_page_accessor.offset_of_first_value();
                     drop(new_page_accessor);
-                    let guard = AccessGuardMut::new(new_page, offset, value.len());
+                    let guard =
+                        with_guard.then(|| AccessGuardMut::new(new_page, offset, value.len()));
                     return if position == 0 {
                         Ok(InsertionResult {
                             new_root: new_page_number,
@@ -248,7 +831,8 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
                         LeafAccessor::new(page_mut.memory(), K::fixed_width(), V::fixed_width());
                     let offset = new_page_accessor.offset_of_value(position).unwrap();
                     drop(new_page_accessor);
-                    let guard = AccessGuardMut::new(page_mut, offset, value.len());
+                    let guard =
+                        with_guard.then(|| AccessGuardMut::new(page_mut, offset, value.len()));
                     return Ok(InsertionResult {
                         new_root: page_number,
                         root_checksum: DEFERRED,
@@ -304,7 +888,8 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
                     let accessor =
                         LeafAccessor::new(new_page.memory(), K::fixed_width(), V::fixed_width());
                     let offset = accessor.offset_of_value(position).unwrap();
-                    let guard = AccessGuardMut::new(new_page, offset, value.len());
+                    let guard =
+                        with_guard.then(|| AccessGuardMut::new(new_page, offset, value.len()));
 
                     InsertionResult {
                         new_root: new_page_number,
@@ -342,14 +927,16 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
                     let accessor =
                         LeafAccessor::new(new_page1.memory(), K::fixed_width(), V::fixed_width());
                     let division = accessor.num_pairs();
-                    let guard = if position < division {
+                    let guard = if !with_guard {
+                        None
+                    } else if position < division {
                         let accessor = LeafAccessor::new(
                             new_page1.memory(),
                             K::fixed_width(),
                             V::fixed_width(),
                         );
                         let offset = accessor.offset_of_value(position).unwrap();
-                        AccessGuardMut::new(new_page1, offset, value.len())
+                        Some(AccessGuardMut::new(new_page1, offset, value.len()))
                     } else {
                         let accessor = LeafAccessor::new(
                             new_page2.memory(),
@@ -357,7 +944,7 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
                             V::fixed_width(),
                         );
                         let offset = accessor.offset_of_value(position - division).unwrap();
-                        AccessGuardMut::new(new_page2, offset, value.len())
+                        Some(AccessGuardMut::new(new_page2, offset, value.len()))
                     };
 
                     InsertionResult {
@@ -369,12 +956,28 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
                     }
                 }
             }
-            BRANCH => {
+            PageType::Branch => {
                 let accessor = BranchAccessor::new(&page, K::fixed_width());
                 let (child_index, child_page) = accessor.child_for_key::<K>(key);
                 let child_checksum = accessor.child_checksum(child_index).unwrap();
-                let sub_result =
-                    self.insert_helper(self.mem.get_page(child_page)?, child_checksum, key, value)?;
+                let sub_result = self.insert_helper(
+                    self.mem.get_page(child_page)?,
+                    child_checksum,
+                    key,
+                    value,
+                    only_if_absent,
+                    with_guard,
+                )?;
+
+                if only_if_absent && sub_result.old_value.is_some() {
+                    return Ok(InsertionResult {
+                        new_root: page.get_page_number(),
+                        root_checksum: page_checksum,
+                        additional_sibling: None,
+                        inserted_value: None,
+                        old_value: sub_result.old_value,
+                    });
+                }
 
                 if sub_result.additional_sibling.is_none()
                     && self.modify_uncommitted
@@ -467,7 +1070,6 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
 
                 result
             }
-            _ => unreachable!(),
         })
     }
 
@@ -875,11 +1477,9 @@ impl<'a, 'b, K: RedbKey, V: RedbValue> MutateHelper<'a, 'b, K, V> {
         checksum: Checksum,
         key: &[u8],
     ) -> Result<(DeletionResult, Option<AccessGuard<'a, V>>)> {
-        let node_mem = page.memory();
-        match node_mem[0] {
-            LEAF => self.delete_leaf_helper(page, checksum, key),
-            BRANCH => self.delete_branch_helper(page, checksum, key),
-            _ => unreachable!(),
+        match check_page_type(&page)? {
+            PageType::Leaf => self.delete_leaf_helper(page, checksum, key),
+            PageType::Branch => self.delete_branch_helper(page, checksum, key),
         }
     }
 }
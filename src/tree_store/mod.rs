@@ -5,18 +5,20 @@ mod btree_mutator;
 mod page_store;
 mod table_tree;
 
-pub(crate) use btree::{Btree, BtreeMut, RawBtree, UntypedBtreeMut};
+#[cfg(feature = "serde")]
+pub use btree::TreeNode;
+pub(crate) use btree::{Btree, BtreeMut, RangeMutPosition, RawBtree, UntypedBtreeMut};
 pub(crate) use btree_base::Checksum;
 pub use btree_base::{AccessGuard, AccessGuardMut};
 pub(crate) use btree_base::{LeafAccessor, LeafMutator, RawLeafBuilder, BRANCH, LEAF};
 pub(crate) use btree_iters::{
     AllPageNumbersBtreeIter, BtreeDrain, BtreeDrainFilter, BtreeRangeIter,
 };
-pub use page_store::Savepoint;
 pub(crate) use page_store::{
     CachePriority, Page, PageHint, PageNumber, SerializedSavepoint, TransactionalMemory,
-    FILE_FORMAT_VERSION, MAX_VALUE_LENGTH, PAGE_SIZE,
+    FILE_FORMAT_VERSION, PAGE_SIZE,
 };
+pub use page_store::{Savepoint, MAX_VALUE_LENGTH};
 pub(crate) use table_tree::{
     FreedPageList, FreedTableKey, InternalTableDefinition, TableTree, TableType,
 };
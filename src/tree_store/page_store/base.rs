@@ -12,7 +12,8 @@ use std::sync::Arc;
 #[cfg(debug_assertions)]
 use std::sync::Mutex;
 
-pub(crate) const MAX_VALUE_LENGTH: usize = 3 * 1024 * 1024 * 1024;
+/// The maximum length in bytes of a key or value
+pub const MAX_VALUE_LENGTH: usize = 3 * 1024 * 1024 * 1024;
 pub(crate) const MAX_PAGE_INDEX: u32 = 0x000F_FFFF;
 
 // On-disk format is:
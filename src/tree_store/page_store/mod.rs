@@ -11,7 +11,8 @@ mod savepoint;
 #[allow(dead_code)]
 mod xxh3;
 
-pub(crate) use base::{Page, PageHint, PageNumber, MAX_VALUE_LENGTH};
+pub use base::MAX_VALUE_LENGTH;
+pub(crate) use base::{Page, PageHint, PageNumber};
 pub(crate) use header::PAGE_SIZE;
 pub(crate) use page_manager::{xxh3_checksum, TransactionalMemory, FILE_FORMAT_VERSION};
 pub use savepoint::Savepoint;
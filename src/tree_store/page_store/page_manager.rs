@@ -17,6 +17,7 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::convert::TryInto;
 use std::fs::File;
+use std::io;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
@@ -25,6 +26,8 @@ use std::sync::Mutex;
 const MAX_USABLE_REGION_SPACE: u64 = 4 * 1024 * 1024 * 1024;
 // TODO: remove this constant?
 pub(crate) const MAX_MAX_PAGE_ORDER: u8 = 20;
+// Region numbers are stored in 20 bits of a PageNumber, which bounds the size of the database
+const MAX_REGIONS: u64 = 0x0010_0000;
 pub(super) const MIN_USABLE_PAGES: u32 = 10;
 const MIN_DESIRED_USABLE_BYTES: u64 = 1024 * 1024;
 
@@ -996,6 +999,53 @@ impl TransactionalMemory {
         Ok(())
     }
 
+    // Grows the database, in a single step, so that at least `pages` pages are free, and allocating
+    // them doesn't need to grow it one step at a time
+    pub(crate) fn reserve(&self, pages: u64) -> Result {
+        let mut state = self.state.lock().unwrap();
+        let layout = state.header.layout();
+        let mut free = 0u64;
+        for i in 0..layout.num_regions() {
+            free += state.get_region(i).count_free_pages() as u64;
+        }
+        if free >= pages {
+            return Ok(());
+        }
+
+        let full_region_layout = layout.full_region_layout();
+        let max_usable_bytes = MAX_REGIONS * full_region_layout.usable_bytes();
+        let desired_usable_bytes = (pages - free)
+            .checked_mul(self.page_size.into())
+            .and_then(|growth| growth.checked_add(layout.usable_bytes()))
+            .filter(|desired| *desired <= max_usable_bytes);
+        let desired_usable_bytes = if let Some(desired) = desired_usable_bytes {
+            desired
+        } else {
+            return Err(StorageError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Reserving {pages} pages would exceed the maximum database size"),
+            )));
+        };
+        let new_layout = DatabaseLayout::calculate(
+            desired_usable_bytes,
+            full_region_layout.num_pages(),
+            self.page_size,
+        );
+
+        if let Err(err) = self.storage.resize(new_layout.len()) {
+            // Nothing else has been modified yet, so the database is still consistent if the file
+            // can be restored to its previous length
+            if self.storage.resize(layout.len()).is_err() {
+                self.needs_recovery.store(true, Ordering::Release);
+            }
+            return Err(err);
+        }
+        state.allocators.resize_to(new_layout);
+        state.header.set_layout(new_layout);
+
+        Ok(())
+    }
+
     pub(crate) fn allocate(
         &self,
         allocation_size: usize,
@@ -1,6 +1,8 @@
-use std::cmp::Ordering;
+use crate::{Result, StorageError};
+use std::cmp::{Ordering, Reverse};
 use std::convert::TryInto;
 use std::fmt::Debug;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 #[derive(Eq, PartialEq, Clone, Debug)]
 enum TypeClassification {
@@ -70,6 +72,14 @@ impl TypeName {
     }
 }
 
+pub(crate) fn invalid_data(type_name: &str, data: &[u8]) -> StorageError {
+    StorageError::Corrupted(format!(
+        "Invalid data for {}: {:?}",
+        type_name,
+        &data[..data.len().min(32)]
+    ))
+}
+
 pub trait RedbValue: Debug {
     /// SelfType<'a> must be the same type as Self with all lifetimes replaced with 'a
     type SelfType<'a>: Debug + 'a
@@ -89,6 +99,16 @@ pub trait RedbValue: Debug {
     where
         Self: 'a;
 
+    /// Deserializes data, returning an error instead of panicking if it is invalid
+    ///
+    /// The default implementation calls [`Self::from_bytes`]
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<Self::SelfType<'a>>
+    where
+        Self: 'a,
+    {
+        Ok(Self::from_bytes(data))
+    }
+
     /// Serialize the value to a slice
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
     where
@@ -149,6 +169,17 @@ impl RedbValue for () {
         ()
     }
 
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<()>
+    where
+        Self: 'a,
+    {
+        if data.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid_data("()", data))
+        }
+    }
+
     fn as_bytes<'a, 'b: 'a>(_: &'a Self::SelfType<'b>) -> &'a [u8]
     where
         Self: 'a,
@@ -168,6 +199,55 @@ impl RedbKey for () {
     }
 }
 
+impl RedbValue for bool {
+    type SelfType<'a> = bool
+    where
+        Self: 'a;
+    type AsBytes<'a> = [u8; 1]
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        Some(1)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> bool
+    where
+        Self: 'a,
+    {
+        Self::try_from_bytes(data).expect("invalid bool")
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<bool>
+    where
+        Self: 'a,
+    {
+        match data {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(invalid_data("bool", data)),
+        }
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> [u8; 1]
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        [u8::from(*value)]
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal("bool")
+    }
+}
+
+impl RedbKey for bool {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::from_bytes(data1).cmp(&Self::from_bytes(data2))
+    }
+}
+
 impl<T: RedbValue> RedbValue for Option<T> {
     type SelfType<'a> = Option<T::SelfType<'a>>
     where
@@ -191,6 +271,17 @@ impl<T: RedbValue> RedbValue for Option<T> {
         }
     }
 
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<Option<T::SelfType<'a>>>
+    where
+        Self: 'a,
+    {
+        match data.first() {
+            Some(0) => Ok(None),
+            Some(1) => T::try_from_bytes(&data[1..]).map(Some),
+            _ => Err(invalid_data(Self::type_name().name(), data)),
+        }
+    }
+
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Vec<u8>
     where
         Self: 'a,
@@ -209,6 +300,139 @@ impl<T: RedbValue> RedbValue for Option<T> {
     }
 }
 
+impl<T: RedbValue> RedbValue for Reverse<T> {
+    type SelfType<'a> = Reverse<T::SelfType<'a>>
+    where
+        Self: 'a;
+    type AsBytes<'a> = T::AsBytes<'a>
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        T::fixed_width()
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Reverse<T::SelfType<'a>>
+    where
+        Self: 'a,
+    {
+        Reverse(T::from_bytes(data))
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<Reverse<T::SelfType<'a>>>
+    where
+        Self: 'a,
+    {
+        T::try_from_bytes(data).map(Reverse)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> T::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        T::as_bytes(&value.0)
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal(&format!("Reverse<{}>", T::type_name().name()))
+    }
+}
+
+impl<T: RedbKey> RedbKey for Reverse<T> {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        T::compare(data2, data1)
+    }
+}
+
+// Vec<T> is stored as the number of elements, as a little-endian u32, followed by the elements.
+// Variable width elements are each prefixed by their length, as a little-endian u32
+impl<T: RedbValue> RedbValue for Vec<T> {
+    type SelfType<'a> = Vec<T::SelfType<'a>>
+    where
+        Self: 'a;
+    type AsBytes<'a> = Vec<u8>
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Vec<T::SelfType<'a>>
+    where
+        Self: 'a,
+    {
+        vec_element_ranges::<T>(data)
+            .unwrap()
+            .into_iter()
+            .map(|range| T::from_bytes(&data[range]))
+            .collect()
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<Vec<T::SelfType<'a>>>
+    where
+        Self: 'a,
+    {
+        vec_element_ranges::<T>(data)
+            .ok_or_else(|| invalid_data(Self::type_name().name(), data))?
+            .into_iter()
+            .map(|range| T::try_from_bytes(&data[range]))
+            .collect()
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Vec<u8>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        let mut result = u32::try_from(value.len()).unwrap().to_le_bytes().to_vec();
+        for element in value.iter() {
+            let bytes = T::as_bytes(element);
+            if T::fixed_width().is_none() {
+                let len = u32::try_from(bytes.as_ref().len()).unwrap();
+                result.extend_from_slice(&len.to_le_bytes());
+            }
+            result.extend_from_slice(bytes.as_ref());
+        }
+        result
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal(&format!("Vec<{}>", T::type_name().name()))
+    }
+}
+
+// Returns the location of each element of a serialized Vec<T>, or None if data is not valid
+fn vec_element_ranges<T: RedbValue>(data: &[u8]) -> Option<Vec<std::ops::Range<usize>>> {
+    let count: usize = u32::from_le_bytes(data.get(..4)?.try_into().unwrap())
+        .try_into()
+        .unwrap();
+    // Don't trust count for the allocation, since data may be corrupted
+    let mut ranges = Vec::with_capacity(count.min(data.len()));
+    let mut offset = 4;
+    for _ in 0..count {
+        let len = if let Some(width) = T::fixed_width() {
+            width
+        } else {
+            let len = u32::from_le_bytes(data.get(offset..(offset + 4))?.try_into().unwrap());
+            offset += 4;
+            len.try_into().unwrap()
+        };
+        let end = offset.checked_add(len)?;
+        if end > data.len() {
+            return None;
+        }
+        ranges.push(offset..end);
+        offset = end;
+    }
+    if offset == data.len() {
+        Some(ranges)
+    } else {
+        None
+    }
+}
+
 impl RedbValue for &[u8] {
     type SelfType<'a> = &'a [u8]
     where
@@ -266,6 +490,14 @@ impl<const N: usize> RedbValue for &[u8; N] {
         data.try_into().unwrap()
     }
 
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<&'a [u8; N]>
+    where
+        Self: 'a,
+    {
+        data.try_into()
+            .map_err(|_| invalid_data(Self::type_name().name(), data))
+    }
+
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a [u8; N]
     where
         Self: 'a,
@@ -285,6 +517,53 @@ impl<const N: usize> RedbKey for &[u8; N] {
     }
 }
 
+impl<const N: usize> RedbValue for [u8; N] {
+    type SelfType<'a> = [u8; N]
+    where
+        Self: 'a;
+    type AsBytes<'a> = &'a [u8; N]
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        Some(N)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> [u8; N]
+    where
+        Self: 'a,
+    {
+        data.try_into().unwrap()
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<[u8; N]>
+    where
+        Self: 'a,
+    {
+        data.try_into()
+            .map_err(|_| invalid_data(Self::type_name().name(), data))
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a [u8; N]
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value
+    }
+
+    // Differs from &[u8; N], so that a table is always opened with the type it was created with
+    fn type_name() -> TypeName {
+        TypeName::internal(&format!("[u8;{N}]:owned"))
+    }
+}
+
+impl<const N: usize> RedbKey for [u8; N] {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
+
 impl RedbValue for &str {
     type SelfType<'a> = &'a str
     where
@@ -304,6 +583,13 @@ impl RedbValue for &str {
         std::str::from_utf8(data).unwrap()
     }
 
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<&'a str>
+    where
+        Self: 'a,
+    {
+        std::str::from_utf8(data).map_err(|_| invalid_data("&str", data))
+    }
+
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a str
     where
         Self: 'a,
@@ -325,6 +611,53 @@ impl RedbKey for &str {
     }
 }
 
+impl RedbValue for String {
+    type SelfType<'a> = String
+    where
+        Self: 'a;
+    type AsBytes<'a> = &'a str
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> String
+    where
+        Self: 'a,
+    {
+        std::str::from_utf8(data).unwrap().to_string()
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<String>
+    where
+        Self: 'a,
+    {
+        std::str::from_utf8(data)
+            .map(|x| x.to_string())
+            .map_err(|_| invalid_data("String", data))
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a str
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_str()
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal("String")
+    }
+}
+
+impl RedbKey for String {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        <&str>::compare(data1, data2)
+    }
+}
+
 macro_rules! be_value {
     ($t:ty) => {
         impl RedbValue for $t {
@@ -342,6 +675,15 @@ macro_rules! be_value {
                 <$t>::from_le_bytes(data.try_into().unwrap())
             }
 
+            fn try_from_bytes<'a>(data: &'a [u8]) -> Result<$t>
+            where
+                Self: 'a,
+            {
+                data.try_into()
+                    .map(<$t>::from_le_bytes)
+                    .map_err(|_| invalid_data(stringify!($t), data))
+            }
+
             fn as_bytes<'a, 'b: 'a>(
                 value: &'a Self::SelfType<'b>,
             ) -> [u8; std::mem::size_of::<$t>()]
@@ -359,6 +701,9 @@ macro_rules! be_value {
     };
 }
 
+// Integers are stored little-endian, so their byte order doesn't match their numeric order.
+// compare() decodes both keys instead, which is a couple of loads, and is cheap next to the page
+// accesses of a lookup
 macro_rules! be_impl {
     ($t:ty) => {
         be_value!($t);
@@ -383,3 +728,126 @@ be_impl!(i64);
 be_impl!(i128);
 be_value!(f32);
 be_value!(f64);
+
+/// Keys are ordered by [`f32::total_cmp`], so that every value, including NaN, has a defined
+/// position. `-0.0` sorts before `0.0`, and NaNs with a positive sign, such as [`f32::NAN`], sort
+/// after infinity
+impl RedbKey for f32 {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::from_bytes(data1).total_cmp(&Self::from_bytes(data2))
+    }
+}
+
+/// Keys are ordered by [`f64::total_cmp`], so that every value, including NaN, has a defined
+/// position. `-0.0` sorts before `0.0`, and NaNs with a positive sign, such as [`f64::NAN`], sort
+/// after infinity
+impl RedbKey for f64 {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::from_bytes(data1).total_cmp(&Self::from_bytes(data2))
+    }
+}
+
+// IP addresses are stored as their big-endian octets, so byte order matches address order
+macro_rules! ip_impl {
+    ($t:ty, $n:expr) => {
+        impl RedbValue for $t {
+            type SelfType<'a> = $t where Self: 'a;
+            type AsBytes<'a> = [u8; $n] where Self: 'a;
+
+            fn fixed_width() -> Option<usize> {
+                Some($n)
+            }
+
+            fn from_bytes<'a>(data: &'a [u8]) -> $t
+            where
+                Self: 'a,
+            {
+                <[u8; $n]>::try_from(data).unwrap().into()
+            }
+
+            fn try_from_bytes<'a>(data: &'a [u8]) -> Result<$t>
+            where
+                Self: 'a,
+            {
+                <[u8; $n]>::try_from(data)
+                    .map(<$t>::from)
+                    .map_err(|_| invalid_data(stringify!($t), data))
+            }
+
+            fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> [u8; $n]
+            where
+                Self: 'a,
+                Self: 'b,
+            {
+                value.octets()
+            }
+
+            fn type_name() -> TypeName {
+                TypeName::internal(stringify!($t))
+            }
+        }
+
+        impl RedbKey for $t {
+            fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+                data1.cmp(data2)
+            }
+        }
+    };
+}
+
+ip_impl!(Ipv4Addr, 4);
+ip_impl!(Ipv6Addr, 16);
+
+// A one byte tag, 4 or 6, followed by the octets of the address. The tag sorts all IPv4
+// addresses before IPv6 addresses, the same as the Ord impl of IpAddr
+impl RedbValue for IpAddr {
+    type SelfType<'a> = IpAddr
+    where
+        Self: 'a;
+    type AsBytes<'a> = Vec<u8>
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> IpAddr
+    where
+        Self: 'a,
+    {
+        Self::try_from_bytes(data).unwrap()
+    }
+
+    fn try_from_bytes<'a>(data: &'a [u8]) -> Result<IpAddr>
+    where
+        Self: 'a,
+    {
+        match data.split_first() {
+            Some((4, octets)) => Ipv4Addr::try_from_bytes(octets).map(IpAddr::V4),
+            Some((6, octets)) => Ipv6Addr::try_from_bytes(octets).map(IpAddr::V6),
+            _ => Err(invalid_data("IpAddr", data)),
+        }
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Vec<u8>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        match value {
+            IpAddr::V4(addr) => [&[4u8], addr.octets().as_slice()].concat(),
+            IpAddr::V6(addr) => [&[6u8], addr.octets().as_slice()].concat(),
+        }
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal("IpAddr")
+    }
+}
+
+impl RedbKey for IpAddr {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
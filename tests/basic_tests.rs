@@ -1,8 +1,10 @@
 use redb::{
     Database, MultimapTableDefinition, MultimapTableHandle, Range, ReadableTable, RedbKey,
-    RedbValue, TableDefinition, TableHandle, TypeName,
+    RedbValue, StorageError, TableDefinition, TableError, TableHandle, TypeName,
 };
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::Bound;
 #[cfg(not(target_os = "wasi"))]
 use std::sync;
 
@@ -128,614 +130,3778 @@ fn drain() {
 }
 
 #[test]
-fn drain_filter() {
+fn remove_reports_presence() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
-        for i in 0..10 {
-            table.insert(&i, &i).unwrap();
-        }
-        // Test draining uncommitted data
-        drop(table.drain_filter(0..10, |k, _| k < 5).unwrap());
-        for i in 0..5 {
-            table.insert(&i, &i).unwrap();
+        assert!(table.remove(&0).unwrap().is_none());
+        for i in 0..1000 {
+            table.insert(&(2 * i), &i).unwrap();
         }
-        assert_eq!(table.len().unwrap(), 10);
-
-        // Test matching on the value
-        drop(table.drain_filter(0..10, |_, v| v < 5).unwrap());
-        for i in 0..5 {
-            table.insert(&i, &i).unwrap();
-        }
-        assert_eq!(table.len().unwrap(), 10);
     }
     write_txn.commit().unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
-        assert_eq!(table.len().unwrap(), 10);
-        for (i, item) in table.drain_filter(0.., |x, _| x < 5).unwrap().enumerate() {
-            let (k, v) = item.unwrap();
-            assert_eq!(i as u64, k.value());
-            assert_eq!(i as u64, v.value());
-        }
-        assert_eq!(table.len().unwrap(), 5);
-        let mut i = 5u64;
-        for item in table.range(0..10).unwrap() {
-            let (k, v) = item.unwrap();
-            assert_eq!(i, k.value());
-            assert_eq!(i, v.value());
-            i += 1;
+        // Every key is removed, so this includes the keys that separate the branch pages
+        for i in 0..1000 {
+            assert!(table.remove(&(2 * i + 1)).unwrap().is_none());
+            assert_eq!(table.remove(&(2 * i)).unwrap().unwrap().value(), i);
+            assert!(table.remove(&(2 * i)).unwrap().is_none());
         }
+        assert!(table.is_empty().unwrap());
     }
-    write_txn.abort().unwrap();
-
-    // Check that dropping the iter early works too
-    let write_txn = db.begin_write().unwrap();
-    {
-        let mut table = write_txn.open_table(U64_TABLE).unwrap();
-        assert_eq!(table.len().unwrap(), 10);
-        drop(table.drain_filter(0.., |x, _| x < 5).unwrap());
-        assert_eq!(table.len().unwrap(), 5);
-    }
-    write_txn.abort().unwrap();
+    write_txn.commit().unwrap();
 }
 
 #[test]
-fn stored_size() {
+fn remove_range() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(STR_TABLE).unwrap();
-        table.insert("hello", "world").unwrap();
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(&i, &i).unwrap();
+        }
+        // Test removing uncommitted data
+        assert_eq!(table.remove_range(100..200).unwrap(), 100);
+        assert_eq!(table.len().unwrap(), 900);
+        assert!(table.get(&150).unwrap().is_none());
+        for i in 100..200 {
+            table.insert(&i, &i).unwrap();
+        }
     }
     write_txn.commit().unwrap();
 
-    let write_txn = db.begin_write().unwrap();
-    assert_eq!(write_txn.stats().unwrap().stored_bytes(), 10);
-    assert!(write_txn.stats().unwrap().fragmented_bytes() > 0);
-    assert!(write_txn.stats().unwrap().metadata_bytes() > 0);
-    write_txn.abort().unwrap();
-}
-
-#[test]
-fn create_open() {
-    let tmpfile = create_tempfile();
-    let db = Database::create(tmpfile.path()).unwrap();
     let write_txn = db.begin_write().unwrap();
     {
         let mut table = write_txn.open_table(U64_TABLE).unwrap();
-        table.insert(&0, &1).unwrap();
+        // Empty range
+        assert_eq!(table.remove_range(500..500).unwrap(), 0);
+        assert_eq!(table.remove_range(2000..).unwrap(), 0);
+        assert_eq!(table.len().unwrap(), 1000);
+
+        assert_eq!(table.remove_range(1..=998).unwrap(), 998);
+        let remaining: Vec<u64> = table
+            .iter()
+            .unwrap()
+            .map(|x| x.unwrap().0.value())
+            .collect();
+        assert_eq!(remaining, vec![0, 999]);
+
+        // Remove everything
+        assert_eq!(table.remove_range::<u64>(..).unwrap(), 2);
+        assert!(table.is_empty().unwrap());
+        table.insert(&5, &5).unwrap();
+        assert_eq!(table.len().unwrap(), 1);
     }
     write_txn.commit().unwrap();
-    drop(db);
-
-    let db2 = Database::open(tmpfile.path()).unwrap();
 
-    let read_txn = db2.begin_read().unwrap();
+    let read_txn = db.begin_read().unwrap();
     let table = read_txn.open_table(U64_TABLE).unwrap();
-    assert_eq!(1, table.get(&0).unwrap().unwrap().value());
+    assert_eq!(table.len().unwrap(), 1);
+    assert_eq!(table.get(&5).unwrap().unwrap().value(), 5);
 }
 
 #[test]
-fn multiple_tables() {
-    let definition1: TableDefinition<&str, &str> = TableDefinition::new("1");
-    let definition2: TableDefinition<&str, &str> = TableDefinition::new("2");
+fn remove_range_multi_level() {
+    use rand::prelude::{Rng, SeedableRng, StdRng};
+    use std::collections::BTreeMap;
 
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut expected: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(definition1).unwrap();
-        let mut table2 = write_txn.open_table(definition2).unwrap();
-
-        table.insert("hello", "world").unwrap();
-        table2.insert("hello", "world2").unwrap();
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..20_000u64 {
+            let value = vec![i.to_le_bytes()[0]; rng.gen_range(0..200)];
+            table.insert(&i, value.as_slice()).unwrap();
+            expected.insert(i, value);
+        }
+        assert!(table.stats().unwrap().tree_height() >= 3);
     }
     write_txn.commit().unwrap();
 
-    let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(definition1).unwrap();
-    let table2 = read_txn.open_table(definition2).unwrap();
-    assert_eq!(table.len().unwrap(), 1);
-    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
-    assert_eq!(table2.len().unwrap(), 1);
-    assert_eq!("world2", table2.get("hello").unwrap().unwrap().value());
+    for round in 0..60 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(definition).unwrap();
+            for _ in 0..3 {
+                let a = rng.gen_range(0..21_000u64);
+                let b = a + rng.gen_range(0..[5, 300, 5000][round % 3]);
+                let range: (Bound<u64>, Bound<u64>) = match rng.gen_range(0..4) {
+                    0 => (Bound::Included(a), Bound::Excluded(b)),
+                    1 => (Bound::Included(a), Bound::Included(b)),
+                    2 => (Bound::Excluded(a), Bound::Included(b)),
+                    _ => (Bound::Unbounded, Bound::Excluded(a / 50)),
+                };
+                let removed = table.remove_range(range).unwrap();
+                let keys: Vec<u64> = expected.range(range).map(|(k, _)| *k).collect();
+                assert_eq!(removed, keys.len() as u64);
+                for key in keys {
+                    expected.remove(&key);
+                }
+                table.verify_structure().unwrap();
+            }
+            assert_eq!(table.len().unwrap(), expected.len() as u64);
+            for (entry, (expected_key, expected_value)) in
+                table.iter().unwrap().zip(expected.iter())
+            {
+                let (key, value) = entry.unwrap();
+                assert_eq!(key.value(), *expected_key);
+                assert_eq!(value.value(), expected_value.as_slice());
+            }
+            // Refill part of the table, so that later rounds have entries to remove
+            for _ in 0..200 {
+                let i = rng.gen_range(0..21_000u64);
+                let value = vec![i.to_le_bytes()[0]; rng.gen_range(0..200)];
+                table.insert(&i, value.as_slice()).unwrap();
+                expected.insert(i, value);
+            }
+        }
+        write_txn.commit().unwrap();
+    }
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        let leaves = table.stats().unwrap().leaf_pages();
+        table.remove_range(100..20_900).unwrap();
+        table.verify_structure().unwrap();
+        assert!(table.stats().unwrap().leaf_pages() * 10 < leaves);
+        let remaining: Vec<u64> = table
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().0.value())
+            .collect();
+        let expected: Vec<u64> = expected
+            .keys()
+            .copied()
+            .filter(|k| !(100..20_900).contains(k))
+            .collect();
+        assert_eq!(remaining, expected);
+    }
+    write_txn.commit().unwrap();
 }
 
 #[test]
-fn list_tables() {
+fn remove_range_with_progress() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let definition_x: TableDefinition<&[u8], &[u8]> = TableDefinition::new("x");
-    let definition_y: TableDefinition<&[u8], &[u8]> = TableDefinition::new("y");
-    let definition_mx: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("mx");
-    let definition_my: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("my");
-
     let write_txn = db.begin_write().unwrap();
     {
-        write_txn.open_table(definition_x).unwrap();
-        write_txn.open_table(definition_y).unwrap();
-        write_txn.open_multimap_table(definition_mx).unwrap();
-        write_txn.open_multimap_table(definition_my).unwrap();
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000u64 {
+            table.insert(&i, &i).unwrap();
+        }
     }
+    write_txn.commit().unwrap();
 
-    let tables: Vec<String> = write_txn
-        .list_tables()
-        .unwrap()
-        .map(|h| h.name().to_string())
-        .collect();
-    let multimap_tables: Vec<String> = write_txn
-        .list_multimap_tables()
-        .unwrap()
-        .map(|h| h.name().to_string())
-        .collect();
-    assert_eq!(tables, &["x", "y"]);
-    assert_eq!(multimap_tables, &["mx", "my"]);
+    let write_txn = db.begin_write().unwrap();
+    let removed = {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let mut calls = vec![];
+        let removed = table
+            .remove_range_with_progress(100..9100, |count| {
+                calls.push(count);
+                count < 3000
+            })
+            .unwrap();
+        // Progress is reported after each chunk, and removal stops after the first chunk for
+        // which it returns false
+        assert!(calls.len() > 1);
+        assert!(calls.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(calls.last(), Some(&removed));
+        assert!(calls[calls.len() - 2] < 3000);
+        assert!((3000..9000).contains(&removed));
+        table.verify_structure().unwrap();
+        removed
+    };
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let tables: Vec<String> = read_txn
-        .list_tables()
-        .unwrap()
-        .map(|h| h.name().to_string())
-        .collect();
-    let multimap_tables: Vec<String> = read_txn
-        .list_multimap_tables()
-        .unwrap()
-        .map(|h| h.name().to_string())
-        .collect();
-    assert_eq!(tables, &["x", "y"]);
-    assert_eq!(multimap_tables, &["mx", "my"]);
-}
-
-#[test]
-// Test that these signatures compile
-fn tuple_type_function_lifetime() {
-    #[allow(dead_code)]
-    fn insert_inferred_lifetime(table: &mut redb::Table<(&str, u8), u64>) {
-        table
-            .insert(&(String::from("hello").as_str(), 8), &1)
-            .unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 10_000 - removed);
+    // Only the start of the range was removed
+    assert_eq!(table.count_range(100..9100).unwrap(), 9000 - removed);
+    {
+        let (first, _) = table.range(100..).unwrap().next().unwrap().unwrap();
+        assert_eq!(first.value(), 100 + removed);
+        let (last, _) = table.range(..100).unwrap().next_back().unwrap().unwrap();
+        assert_eq!(last.value(), 99);
     }
+    drop(table);
+    drop(read_txn);
 
-    #[allow(dead_code)]
-    #[allow(clippy::needless_lifetimes)]
-    fn insert_explicit_lifetime<'a>(table: &mut redb::Table<(&'a str, u8), u64>) {
-        table
-            .insert(&(String::from("hello").as_str(), 8), &1)
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let mut last = 0;
+        let remaining = table
+            .remove_range_with_progress(100..9100, |count| {
+                last = count;
+                true
+            })
             .unwrap();
+        assert_eq!(remaining, 9000 - removed);
+        assert_eq!(last, remaining);
+        assert_eq!(table.count_range(100..9100).unwrap(), 0);
+        assert_eq!(table.len().unwrap(), 1000);
+        table.verify_structure().unwrap();
+        assert_eq!(
+            table
+                .remove_range_with_progress(100..9100, |_| true)
+                .unwrap(),
+            0
+        );
     }
+    write_txn.commit().unwrap();
 }
 
 #[test]
-fn tuple_type_lifetime() {
+fn clear() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<(&str, u8), (u16, u32)> = TableDefinition::new("table");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    let before = write_txn.stats().unwrap();
+    assert!(before.tree_height() > 1);
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.clear().unwrap();
+        assert!(table.is_empty().unwrap());
+        assert!(table.get(&5).unwrap().is_none());
+    }
+    let after = write_txn.stats().unwrap();
+    // All the pages were uncommitted, so they should have been freed immediately
+    assert_eq!(after.leaf_pages(), 0);
+    assert_eq!(
+        before.allocated_pages() - after.allocated_pages(),
+        before.leaf_pages() + before.branch_pages() - after.branch_pages()
+    );
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table
-            .insert(&(String::from("hello").as_str(), 5), &(0, 123))
-            .unwrap();
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.clear().unwrap();
+        table.insert(&1, &1).unwrap();
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(table.get(&("hello", 5)).unwrap().unwrap().value(), (0, 123));
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 1);
+    assert_eq!(table.get(&1).unwrap().unwrap().value(), 1);
 }
 
 #[test]
-fn tuple2_type() {
+fn root_collapses_when_shrinking() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<(&str, u8), (u16, u32)> = TableDefinition::new("table");
-
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table.insert(&("hello", 5), &(0, 123)).unwrap();
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            table.insert(&i, &i).unwrap();
+        }
     }
     write_txn.commit().unwrap();
 
+    // Remove from both ends, across several transactions. A branch root must always keep at least
+    // two children, so the tree loses a level as soon as its root would have only one
+    let mut remaining = 0..10_000u64;
+    while remaining.end - remaining.start > 1 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            for _ in 0..500 {
+                if remaining.end - remaining.start == 1 {
+                    break;
+                }
+                let key = if remaining.start % 2 == 0 {
+                    remaining.next().unwrap()
+                } else {
+                    remaining.next_back().unwrap()
+                };
+                table.remove(&key).unwrap();
+                if key % 50 == 0 {
+                    let stats = table.stats().unwrap();
+                    if let Some(children) = stats.pages_per_level().get(1) {
+                        assert!(*children >= 2);
+                    }
+                }
+            }
+        }
+        write_txn.commit().unwrap();
+    }
+
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(table.get(&("hello", 5)).unwrap().unwrap().value(), (0, 123));
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let stats = table.stats().unwrap();
+    assert_eq!(stats.tree_height(), 1);
+    assert_eq!(stats.branch_pages(), 0);
+    assert_eq!(table.len().unwrap(), 1);
 }
 
 #[test]
-fn tuple3_type() {
+fn table_stats() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<(&str, u8, u16), (u16, u32)> = TableDefinition::new("table");
-
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table.insert(&("hello", 5, 6), &(0, 123)).unwrap();
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let stats = table.stats().unwrap();
+        assert_eq!(stats.tree_height(), 0);
+        assert!(stats.pages_per_level().is_empty());
+
+        table.insert(&0, &0).unwrap();
+        let stats = table.stats().unwrap();
+        assert_eq!(stats.tree_height(), 1);
+        assert_eq!(stats.pages_per_level(), &[1]);
+
+        for i in 1..10_000 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    let db_stats = write_txn.stats().unwrap();
+    let table_stats = write_txn.open_table(U64_TABLE).unwrap().stats().unwrap();
+    assert!(table_stats.tree_height() > 1);
+    let levels = table_stats.pages_per_level();
+    assert_eq!(levels.len(), table_stats.tree_height() as usize);
+    assert_eq!(levels[0], 1);
+    assert_eq!(*levels.last().unwrap(), db_stats.leaf_pages());
+    for window in levels.windows(2) {
+        assert!(window[0] < window[1]);
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(
-        table.get(&("hello", 5, 6)).unwrap().unwrap().value(),
-        (0, 123)
-    );
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.stats().unwrap().pages_per_level(), levels);
 }
 
 #[test]
-fn tuple4_type() {
+fn table_storage_stats() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<(&str, u8, u16, u32), (u16, u32)> =
-        TableDefinition::new("table");
-
     let write_txn = db.begin_write().unwrap();
-    {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table.insert(&("hello", 5, 6, 7), &(0, 123)).unwrap();
+    let mut table = write_txn.open_table(U64_TABLE).unwrap();
+    let stats = table.stats().unwrap();
+    assert_eq!(stats.leaf_pages(), 0);
+    assert_eq!(stats.branch_pages(), 0);
+    assert_eq!(stats.stored_bytes(), 0);
+    assert_eq!(stats.metadata_bytes(), 0);
+    assert_eq!(stats.fragmented_bytes(), 0);
+
+    // A single leaf of fixed width pairs only has a 4 byte header
+    for i in 0..10u64 {
+        table.insert(&i, &i).unwrap();
     }
-    write_txn.commit().unwrap();
+    let stats = table.stats().unwrap();
+    assert_eq!(stats.leaf_pages(), 1);
+    assert_eq!(stats.branch_pages(), 0);
+    assert_eq!(stats.stored_bytes(), 10 * 16);
+    assert_eq!(stats.metadata_bytes(), 4);
+    assert_eq!(
+        stats.stored_bytes() + stats.metadata_bytes() + stats.fragmented_bytes(),
+        4096
+    );
 
-    let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
+    for i in 10..10_000u64 {
+        table.insert(&i, &i).unwrap();
+    }
+    let stats = table.stats().unwrap();
+    let levels = stats.pages_per_level();
+    assert_eq!(stats.leaf_pages(), *levels.last().unwrap());
     assert_eq!(
-        table.get(&("hello", 5, 6, 7)).unwrap().unwrap().value(),
-        (0, 123)
+        stats.branch_pages(),
+        levels[..levels.len() - 1].iter().sum::<u64>()
     );
+    assert_eq!(stats.stored_bytes(), 10_000 * 16);
+    let total_bytes = stats.stored_bytes() + stats.metadata_bytes() + stats.fragmented_bytes();
+    assert_eq!(
+        total_bytes,
+        (stats.leaf_pages() + stats.branch_pages()) * 4096
+    );
+
+    // Removing entries leaves slack in the remaining pages
+    for i in (0..10_000u64).step_by(2) {
+        table.remove(&i).unwrap();
+    }
+    let sparse_stats = table.stats().unwrap();
+    assert_eq!(sparse_stats.stored_bytes(), 5_000 * 16);
+    assert!(sparse_stats.fragmented_bytes() > stats.fragmented_bytes());
 }
 
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple5_type() {
+fn compact_table() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
+    let values: Vec<String> = (0..10_000u64).map(|i| format!("value_{i}")).collect();
+    let definition: TableDefinition<u64, &str> = TableDefinition::new("x");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.compact().unwrap();
+        assert!(table.is_empty().unwrap());
+        for i in 0..10_000u64 {
+            table.insert(i, values[i as usize].as_str()).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
 
-    let table_def: TableDefinition<(&str, u8, u16, u32, u64), (u16, u32)> =
-        TableDefinition::new("table");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in (0..10_000u64).filter(|i| i % 4 != 0) {
+            table.remove(i).unwrap();
+        }
+        let sparse_stats = table.stats().unwrap();
+        table.compact().unwrap();
+        let stats = table.stats().unwrap();
+        assert!(stats.leaf_pages() < sparse_stats.leaf_pages());
+        assert!(stats.fragmented_bytes() < sparse_stats.fragmented_bytes());
+        assert_eq!(stats.stored_bytes(), sparse_stats.stored_bytes());
+        table.verify_structure().unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(table.len().unwrap(), 2_500);
+    for (i, entry) in (0..10_000u64).step_by(4).zip(table.iter().unwrap()) {
+        let (key, value) = entry.unwrap();
+        assert_eq!(key.value(), i);
+        assert_eq!(value.value(), values[i as usize]);
+    }
+}
 
+#[test]
+fn retain() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let values: Vec<String> = (0..10_000u64).map(|i| format!("value_{i}")).collect();
+    let definition: TableDefinition<u64, &str> = TableDefinition::new("x");
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table.insert(&("hello", 5, 6, 7, 8), &(0, 123)).unwrap();
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..10_000u64 {
+            table.insert(i, values[i as usize].as_str()).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        let full_stats = table.stats().unwrap();
+        table
+            .retain(|key, value| key % 3 == 0 && value.ends_with('0'))
+            .unwrap();
+        let stats = table.stats().unwrap();
+        assert!(stats.leaf_pages() < full_stats.leaf_pages());
+        table.verify_structure().unwrap();
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(
-        table.get(&("hello", 5, 6, 7, 8)).unwrap().unwrap().value(),
-        (0, 123)
-    );
+    let table = read_txn.open_table(definition).unwrap();
+    let expected: Vec<u64> = (0..10_000u64).filter(|i| i % 30 == 0).collect();
+    assert_eq!(table.len().unwrap(), expected.len() as u64);
+    for (i, entry) in expected.iter().zip(table.iter().unwrap()) {
+        let (key, value) = entry.unwrap();
+        assert_eq!(key.value(), *i);
+        assert_eq!(value.value(), values[*i as usize]);
+    }
+    drop(table);
+    drop(read_txn);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.retain(|_, _| false).unwrap();
+        assert!(table.is_empty().unwrap());
+        assert_eq!(table.stats().unwrap().leaf_pages(), 0);
+        table.insert(1, "hello").unwrap();
+        assert_eq!(table.get(1).unwrap().unwrap().value(), "hello");
+    }
+    write_txn.commit().unwrap();
 }
 
+// A panic in the predicate must leave the table unchanged and usable
+#[cfg(not(target_os = "wasi"))]
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple6_type() {
+fn retain_predicate_panic() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            table.insert(i, i).unwrap();
+        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            table
+                .retain(|key, _| {
+                    assert!(key < 500);
+                    true
+                })
+                .unwrap();
+        }));
+        assert!(result.is_err());
+        assert_eq!(table.len().unwrap(), 1000);
+        table.retain(|key, _| key < 500).unwrap();
+        assert_eq!(table.len().unwrap(), 500);
+        table.verify_structure().unwrap();
+    }
+    write_txn.commit().unwrap();
+}
 
-    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128), (u16, u32)> =
-        TableDefinition::new("table");
+#[test]
+fn range_alternating_ends() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    for len in [0u64, 1, 2, 3, 10, 1000] {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            table.remove_range::<u64>(..).unwrap();
+            for i in 0..len {
+                table.insert(&i, &i).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(U64_TABLE).unwrap();
+        for (start, end) in [(0, len), (1, len), (len / 3, len - len / 4)] {
+            let mut iter = table.range(start..end).unwrap();
+            let mut front = vec![];
+            let mut back = vec![];
+            loop {
+                let item = if (front.len() + back.len()) % 2 == 0 {
+                    iter.next().map(|x| front.push(x.unwrap().0.value()))
+                } else {
+                    iter.next_back().map(|x| back.push(x.unwrap().0.value()))
+                };
+                if item.is_none() {
+                    break;
+                }
+            }
+            // Both ends stay exhausted once they've met
+            assert!(iter.next().is_none());
+            assert!(iter.next_back().is_none());
+            back.reverse();
+            front.extend(back);
+            assert_eq!(front, (start..end).collect::<Vec<u64>>());
+        }
+    }
+}
 
+#[test]
+fn range_keys() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let value = vec![0xAB; 10_000];
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table.insert(&("hello", 5, 6, 7, 8, 9), &(0, 123)).unwrap();
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..100u64 {
+            table.insert(&i, value.as_slice()).unwrap();
+        }
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let keys: Vec<u64> = table
+        .iter()
+        .unwrap()
+        .keys()
+        .map(|x| x.unwrap().value())
+        .collect();
+    assert_eq!(keys, (0..100).collect::<Vec<u64>>());
+
+    let mut keys = table.range(10..20).unwrap().keys();
+    assert_eq!(keys.next().unwrap().unwrap().value(), 10);
+    assert_eq!(keys.next_back().unwrap().unwrap().value(), 19);
+    assert_eq!(keys.count(), 8);
+
+    // Seeking before converting the iterator
+    let mut range = table.range::<u64>(..).unwrap();
+    range.seek(95).unwrap();
+    let keys: Vec<u64> = range.keys().rev().map(|x| x.unwrap().value()).collect();
+    assert_eq!(keys, vec![99, 98, 97, 96, 95]);
+}
+
+#[test]
+fn range_map_entries() {
+    #[derive(Debug, PartialEq)]
+    struct Row {
+        id: u64,
+        name: String,
+    }
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, &str> = TableDefinition::new("x");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(1, "one").unwrap();
+        table.insert(2, "two").unwrap();
+        table.insert(3, "three").unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let to_row = |key: &[u8], value: &[u8]| Row {
+        id: u64::from_le_bytes(key.try_into().unwrap()),
+        name: std::str::from_utf8(value).unwrap().to_string(),
+    };
+    let rows: Vec<Row> = table
+        .range(2..)
+        .unwrap()
+        .map_entries(to_row)
+        .map(|x| x.unwrap())
+        .collect();
     assert_eq!(
-        table
-            .get(&("hello", 5, 6, 7, 8, 9))
-            .unwrap()
-            .unwrap()
-            .value(),
-        (0, 123)
+        rows,
+        vec![
+            Row {
+                id: 2,
+                name: "two".to_string()
+            },
+            Row {
+                id: 3,
+                name: "three".to_string()
+            },
+        ]
     );
+
+    let mut ids = table.iter().unwrap().map_entries(|key, _| key.to_vec());
+    assert_eq!(ids.next_back().unwrap().unwrap(), 3u64.to_le_bytes());
+    assert_eq!(ids.count(), 2);
 }
 
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple7_type() {
+fn insert_raw_copy() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
+    let source: TableDefinition<&str, u64> = TableDefinition::new("source");
+    let destination: TableDefinition<&str, u64> = TableDefinition::new("destination");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(source).unwrap();
+        for i in 0..1000u64 {
+            table.insert(format!("key{i}").as_str(), &i).unwrap();
+        }
+    }
+    {
+        let source = write_txn.open_table(source).unwrap();
+        let mut destination = write_txn.open_table(destination).unwrap();
+        destination.insert("key5", &0).unwrap();
+        let entries = source
+            .iter()
+            .unwrap()
+            .map_entries(|key, value| (key.to_vec(), value.to_vec()));
+        for entry in entries {
+            let (key, value) = entry.unwrap();
+            destination.insert_raw(&key, &value).unwrap();
+        }
+        assert_eq!(destination.get("key5").unwrap().unwrap().value(), 5);
+    }
+    write_txn.commit().unwrap();
 
-    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8), (u16, u32)> =
-        TableDefinition::new("table");
+    let read_txn = db.begin_read().unwrap();
+    let source = read_txn.open_table(source).unwrap();
+    let destination = read_txn.open_table(destination).unwrap();
+    assert_eq!(destination.len().unwrap(), 1000);
+    for (a, b) in source.iter().unwrap().zip(destination.iter().unwrap()) {
+        let (a_key, a_value) = a.unwrap();
+        let (b_key, b_value) = b.unwrap();
+        assert_eq!(a_key.value(), b_key.value());
+        assert_eq!(a_value.value(), b_value.value());
+    }
+}
+
+#[test]
+#[should_panic(expected = "insert_raw() requires values of 8 bytes")]
+fn insert_raw_fixed_width() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    let mut table = write_txn.open_table(U64_TABLE).unwrap();
+    table.insert_raw(&0u64.to_le_bytes(), &[0; 4]).unwrap();
+}
 
+#[test]
+fn value_substring_search() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table
-            .insert(&("hello", 5, 6, 7, 8, 9, -1), &(0, 123))
-            .unwrap();
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..1000u64 {
+            let mut value = vec![b'a'; 100];
+            if i % 7 == 0 {
+                // Place the marker at varying offsets, including the start and end
+                let offset = (i as usize) % 95;
+                value[offset..(offset + 5)].copy_from_slice(b"MARK!");
+            }
+            table.insert(&i, value.as_slice()).unwrap();
+        }
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(
-        table
-            .get(&("hello", 5, 6, 7, 8, 9, -1))
+    let table = read_txn.open_table(definition).unwrap();
+    let needle = b"MARK!";
+    let matches: Vec<u64> = table
+        .range(100..500)
+        .unwrap()
+        .map_entries(|key, value| {
+            let found = value.windows(needle.len()).any(|x| x == needle);
+            (u64::from_le_bytes(key.try_into().unwrap()), found)
+        })
+        .map(|x| x.unwrap())
+        .filter(|(_, found)| *found)
+        .map(|(key, _)| key)
+        .collect();
+    let expected: Vec<u64> = (100..500).filter(|i| i % 7 == 0).collect();
+    assert_eq!(matches, expected);
+}
+
+#[test]
+fn range_next_batch() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..35u64 {
+            table.insert(&i, &(i * 10)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let mut iter = table.iter().unwrap();
+    let mut batches = vec![];
+    loop {
+        let batch = iter.next_batch(10).unwrap();
+        if batch.is_empty() {
+            break;
+        }
+        batches.push(batch);
+    }
+    let sizes: Vec<usize> = batches.iter().map(|x| x.len()).collect();
+    assert_eq!(sizes, vec![10, 10, 10, 5]);
+    let (key, value) = &batches[3][4];
+    assert_eq!(key.as_slice(), 34u64.to_le_bytes());
+    assert_eq!(value.as_slice(), 340u64.to_le_bytes());
+
+    // Batches and individual entries can be mixed
+    let mut iter = table.range(5..).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 5);
+    let batch = iter.next_batch(2).unwrap();
+    assert_eq!(batch[0].0, 6u64.to_le_bytes());
+    assert_eq!(batch[1].0, 7u64.to_le_bytes());
+    assert_eq!(iter.next_back().unwrap().unwrap().0.value(), 34);
+    assert_eq!(iter.next_batch(100).unwrap().len(), 26);
+}
+
+#[test]
+fn range_values() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            table.insert(&i, &(i * i % 997)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let sum: u64 = table
+        .range(100..900)
+        .unwrap()
+        .values()
+        .map(|x| x.unwrap().value())
+        .sum();
+    let mut expected = 0;
+    for entry in table.range(100..900).unwrap() {
+        expected += entry.unwrap().1.value();
+    }
+    assert_eq!(sum, expected);
+
+    let values: Vec<u64> = table
+        .range(10..13)
+        .unwrap()
+        .values()
+        .rev()
+        .map(|x| x.unwrap().value())
+        .collect();
+    assert_eq!(values, vec![144, 121, 100]);
+}
+
+#[test]
+fn count_range() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(table.count_range::<u64>(..).unwrap(), 0);
+        for i in 0..10_000u64 {
+            table.insert(&(2 * i), &i).unwrap();
+        }
+        assert_eq!(table.count_range(100..200).unwrap(), 50);
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.count_range::<u64>(..).unwrap(), 10_000);
+    assert_eq!(table.count_range::<u64>(..).unwrap(), table.len().unwrap());
+    for (start, end) in [
+        (0, 0),
+        (1, 2),
+        (3, 1),
+        (0, 19_999),
+        (999, 15_000),
+        (20_000, 30_000),
+    ] {
+        let expected = table.range(start..end).unwrap().count() as u64;
+        assert_eq!(table.count_range(start..end).unwrap(), expected);
+        let expected = table.range(start..=end).unwrap().count() as u64;
+        assert_eq!(table.count_range(start..=end).unwrap(), expected);
+        let expected = table.range(start..).unwrap().count() as u64;
+        assert_eq!(table.count_range(start..).unwrap(), expected);
+        let expected = table.range(..end).unwrap().count() as u64;
+        assert_eq!(table.count_range(..end).unwrap(), expected);
+    }
+    assert_eq!(table.count_range(0..=0).unwrap(), 1);
+    assert_eq!(table.count_range(0..20_000).unwrap(), 10_000);
+    assert_eq!(table.count_range(1..19_999).unwrap(), 9_999);
+}
+
+#[test]
+fn select_nth_and_rank() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert!(table.select_nth(0).unwrap().is_none());
+        assert_eq!(table.rank(5).unwrap(), 0);
+        for i in 0..10_000u64 {
+            table.insert(&(2 * i), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    for n in (0..10_000u64).step_by(37).chain([9_999]) {
+        let (key, value) = table.select_nth(n).unwrap().unwrap();
+        assert_eq!(key.value(), 2 * n);
+        assert_eq!(value.value(), n);
+        assert_eq!(table.rank(key.value()).unwrap(), n);
+        // Keys which aren't present rank after every smaller key
+        assert_eq!(table.rank(key.value() + 1).unwrap(), n + 1);
+    }
+    assert!(table.select_nth(10_000).unwrap().is_none());
+    assert!(table.select_nth(u64::MAX).unwrap().is_none());
+    assert_eq!(table.rank(u64::MAX).unwrap(), 10_000);
+}
+
+#[test]
+fn resume_range_after_deletion() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    // Page through the table, deleting the last returned key before each page is requested. A
+    // scan resumes from the key it stopped at, which needn't still exist
+    let mut forward = vec![];
+    let mut resume_after: Option<u64> = None;
+    loop {
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(U64_TABLE).unwrap();
+        let start = resume_after.map_or(Bound::Unbounded, Bound::Excluded);
+        let page: Vec<u64> = table
+            .range((start, Bound::Unbounded))
             .unwrap()
+            .take(100)
+            .map(|x| x.unwrap().0.value())
+            .collect();
+        if page.is_empty() {
+            break;
+        }
+        forward.extend_from_slice(&page);
+        resume_after = page.last().copied();
+
+        let write_txn = db.begin_write().unwrap();
+        write_txn
+            .open_table(U64_TABLE)
             .unwrap()
-            .value(),
-        (0, 123)
-    );
+            .remove(resume_after.unwrap())
+            .unwrap();
+        write_txn.commit().unwrap();
+    }
+    assert_eq!(forward, (0..1000).collect::<Vec<u64>>());
+
+    // Page backwards through what remains
+    let mut backward = vec![];
+    let mut resume_before: Option<u64> = None;
+    loop {
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(U64_TABLE).unwrap();
+        let end = resume_before.map_or(Bound::Unbounded, Bound::Excluded);
+        let page: Vec<u64> = table
+            .range((Bound::Unbounded, end))
+            .unwrap()
+            .rev()
+            .take(100)
+            .map(|x| x.unwrap().0.value())
+            .collect();
+        if page.is_empty() {
+            break;
+        }
+        backward.extend_from_slice(&page);
+        resume_before = page.last().copied();
+
+        let write_txn = db.begin_write().unwrap();
+        write_txn
+            .open_table(U64_TABLE)
+            .unwrap()
+            .remove(resume_before.unwrap())
+            .unwrap();
+        write_txn.commit().unwrap();
+    }
+    let expected: Vec<u64> = (0..1000).rev().filter(|i| i % 100 != 99).collect();
+    assert_eq!(backward, expected);
+}
+
+#[test]
+fn separator_keys() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert!(table.separator_keys(usize::MAX).unwrap().is_empty());
+        table.insert(&0, &0).unwrap();
+        // A single leaf has no separators
+        assert!(table.separator_keys(usize::MAX).unwrap().is_empty());
+        for i in 1..50_000u64 {
+            table.insert(&(i * 3), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let stats = table.stats().unwrap();
+    let levels = stats.pages_per_level();
+    assert!(levels.len() > 2);
+    assert!(table.separator_keys(0).unwrap().is_empty());
+    let all: Vec<u64> = table
+        .separator_keys(usize::MAX)
+        .unwrap()
+        .iter()
+        .map(|x| x.value())
+        .collect();
+    // Every leaf but the last is bounded by a separator
+    assert_eq!(all.len() as u64, stats.leaf_pages() - 1);
+    assert!(all.windows(2).all(|x| x[0] < x[1]));
+    // Each separator bounds a non-empty subtree
+    let mut previous = Bound::Unbounded;
+    for key in all.iter() {
+        let count = table
+            .count_range((previous, Bound::Included(*key)))
+            .unwrap();
+        assert!(count > 0);
+        previous = Bound::Excluded(*key);
+    }
+    assert!(table.count_range((previous, Bound::Unbounded)).unwrap() > 0);
+
+    // The separators of the upper levels are a subset of those of the lower ones
+    for (depth, pages) in levels.iter().enumerate().skip(1) {
+        let keys: Vec<u64> = table
+            .separator_keys(depth)
+            .unwrap()
+            .iter()
+            .map(|x| x.value())
+            .collect();
+        assert_eq!(keys.len() as u64, pages - 1);
+        assert!(keys.iter().all(|x| all.binary_search(x).is_ok()));
+    }
+}
+
+#[test]
+fn split_keys() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert!(table.split_keys(4).unwrap().is_empty());
+        for i in 0..3u64 {
+            table.insert(&i, &i).unwrap();
+        }
+        // There aren't enough entries for every range to be non-empty
+        let keys: Vec<u64> = table
+            .split_keys(8)
+            .unwrap()
+            .iter()
+            .map(|x| x.value())
+            .collect();
+        assert_eq!(keys, vec![1, 2]);
+        for i in 3..10_000u64 {
+            table.insert(&(i * 3), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert!(table.split_keys(0).unwrap().is_empty());
+    assert!(table.split_keys(1).unwrap().is_empty());
+    for n in [2u64, 3, 7, 16] {
+        let keys: Vec<u64> = table
+            .split_keys(n)
+            .unwrap()
+            .iter()
+            .map(|x| x.value())
+            .collect();
+        assert_eq!(keys.len() as u64, n - 1);
+        let mut bounds = vec![Bound::Unbounded];
+        bounds.extend(keys.iter().map(|x| Bound::Included(*x)));
+        bounds.push(Bound::Unbounded);
+
+        // The ranges are contiguous and cover every entry exactly once
+        let mut seen = vec![];
+        for window in bounds.windows(2) {
+            let end = match window[1] {
+                Bound::Included(x) => Bound::Excluded(x),
+                other => other,
+            };
+            let range: Vec<u64> = table
+                .range((window[0], end))
+                .unwrap()
+                .map(|x| x.unwrap().0.value())
+                .collect();
+            let expected = 10_000 / n;
+            assert!(range.len() as u64 >= expected - 1 && range.len() as u64 <= expected + 1);
+            seen.extend(range);
+        }
+        let all: Vec<u64> = table
+            .iter()
+            .unwrap()
+            .map(|x| x.unwrap().0.value())
+            .collect();
+        assert_eq!(seen, all);
+    }
+}
+
+#[test]
+fn range_seek() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000u64 {
+            table.insert(&(2 * i), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let mut iter = table.range(100..15_000).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 100);
+    // Seek past several leaves
+    iter.seek(8_000).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 8_000);
+    // Keys which aren't present
+    iter.seek(9_001).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 9_002);
+    // Seeking backwards is a no-op
+    iter.seek(0).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 9_004);
+    iter.seek(9_004).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 9_006);
+
+    iter.seek_back(12_001).unwrap();
+    assert_eq!(iter.next_back().unwrap().unwrap().0.value(), 12_000);
+    iter.seek_back(14_000).unwrap();
+    assert_eq!(iter.next_back().unwrap().unwrap().0.value(), 11_998);
+    iter.seek_back(9_010).unwrap();
+    assert_eq!(iter.next_back().unwrap().unwrap().0.value(), 9_010);
+    let remaining: Vec<u64> = iter.map(|x| x.unwrap().0.value()).collect();
+    assert_eq!(remaining, vec![9_008]);
+
+    // The range bounds are still respected
+    let mut iter = table.range(100..15_000).unwrap();
+    iter.seek(15_000).unwrap();
+    assert!(iter.next().is_none());
+    assert!(iter.next_back().is_none());
+    let mut iter = table.range(100..15_000).unwrap();
+    iter.seek(14_998).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 14_998);
+    assert!(iter.next().is_none());
+    let mut iter = table.range(100..15_000).unwrap();
+    iter.seek_back(99).unwrap();
+    assert!(iter.next_back().is_none());
+    assert!(iter.next().is_none());
+    let mut iter = table.range::<u64>(..).unwrap();
+    iter.seek(5_000).unwrap();
+    iter.seek_back(5_003).unwrap();
+    let remaining: Vec<u64> = iter.rev().map(|x| x.unwrap().0.value()).collect();
+    assert_eq!(remaining, vec![5_002, 5_000]);
+}
+
+#[derive(Clone, Debug)]
+enum DifferentialOp {
+    Insert(u64, usize),
+    Remove(u64),
+    Get(u64),
+    Range(u64, u64, bool),
+    Commit,
+}
+
+fn differential_ops(seed: u64, len: usize) -> Vec<DifferentialOp> {
+    use rand::prelude::{Rng, SeedableRng, StdRng};
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..len)
+        .map(|_| {
+            let key = rng.gen_range(0..500u64);
+            match rng.gen_range(0..100) {
+                0..=44 => DifferentialOp::Insert(key, rng.gen_range(0..700)),
+                45..=74 => DifferentialOp::Remove(key),
+                75..=87 => DifferentialOp::Get(key),
+                88..=97 => {
+                    let end = key + rng.gen_range(0..100);
+                    DifferentialOp::Range(key, end, rng.gen())
+                }
+                _ => DifferentialOp::Commit,
+            }
+        })
+        .collect()
+}
+
+// Applies the operations to both a table and a BTreeMap, and returns the index of the first
+// operation after which they disagree
+fn run_differential(ops: &[DifferentialOp]) -> Result<(), usize> {
+    use std::collections::BTreeMap;
+
+    let definition: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let mut reference: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+    let mut write_txn = db.begin_write().unwrap();
+
+    for (i, op) in ops.iter().enumerate() {
+        let matches = {
+            let mut table = write_txn.open_table(definition).unwrap();
+            match *op {
+                DifferentialOp::Insert(key, len) => {
+                    let value = vec![(key ^ len as u64) as u8; len];
+                    let old = table.insert(key, value.as_slice()).unwrap();
+                    old.map(|x| x.value().to_vec()) == reference.insert(key, value)
+                }
+                DifferentialOp::Remove(key) => {
+                    let old = table.remove(key).unwrap();
+                    old.map(|x| x.value().to_vec()) == reference.remove(&key)
+                }
+                DifferentialOp::Get(key) => {
+                    let value = table.get(key).unwrap().map(|x| x.value().to_vec());
+                    value.as_ref() == reference.get(&key)
+                }
+                DifferentialOp::Range(start, end, reverse) => {
+                    let iter = table.range(start..end).unwrap().map(|x| {
+                        let (key, value) = x.unwrap();
+                        (key.value(), value.value().to_vec())
+                    });
+                    let expected = reference
+                        .range(start..end)
+                        .map(|(key, value)| (*key, value.clone()));
+                    if reverse {
+                        iter.rev().eq(expected.rev())
+                    } else {
+                        iter.eq(expected)
+                    }
+                }
+                DifferentialOp::Commit => {
+                    let contents = table.iter().unwrap().map(|x| {
+                        let (key, value) = x.unwrap();
+                        (key.value(), value.value().to_vec())
+                    });
+                    contents.eq(reference.iter().map(|(key, value)| (*key, value.clone())))
+                        && table.len().unwrap() == reference.len() as u64
+                }
+            }
+        };
+        if !matches {
+            return Err(i);
+        }
+        if matches!(op, DifferentialOp::Commit) {
+            write_txn.commit().unwrap();
+            write_txn = db.begin_write().unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    Ok(())
+}
+
+// Drops operations from a failing sequence, one at a time, for as long as it still fails
+fn shrink_differential(mut ops: Vec<DifferentialOp>) -> Vec<DifferentialOp> {
+    let mut i = 0;
+    while i < ops.len() {
+        let mut candidate = ops.clone();
+        candidate.remove(i);
+        if let Err(failed) = run_differential(&candidate) {
+            candidate.truncate(failed + 1);
+            ops = candidate;
+        } else {
+            i += 1;
+        }
+    }
+    ops
+}
+
+// Set REDB_DIFFERENTIAL_SEED to reproduce a single failing seed
+#[test]
+fn differential_against_btreemap() {
+    let seeds = match std::env::var("REDB_DIFFERENTIAL_SEED") {
+        Ok(seed) => vec![seed.parse().unwrap()],
+        Err(_) => (0..8).collect(),
+    };
+    for seed in seeds {
+        let ops = differential_ops(seed, 2000);
+        if let Err(failed) = run_differential(&ops) {
+            let shrunk = shrink_differential(ops[..=failed].to_vec());
+            panic!("Seed {seed} diverged from BTreeMap. Minimal operations: {shrunk:?}");
+        }
+    }
+}
+
+// With the paranoid feature, every modification below also verifies the table's structure
+#[cfg(feature = "paranoid")]
+#[test]
+fn paranoid_random_operations() {
+    use rand::prelude::{Rng, SeedableRng, StdRng};
+    use std::collections::BTreeMap;
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut reference: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+
+    for _ in 0..20 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(definition).unwrap();
+            for _ in 0..250 {
+                let key = rng.gen_range(0..1000u64);
+                if rng.gen_bool(0.6) {
+                    let value = vec![rng.gen::<u8>(); rng.gen_range(0..600)];
+                    let old = table.insert(key, value.as_slice()).unwrap();
+                    let expected = reference.insert(key, value);
+                    assert_eq!(old.map(|x| x.value().to_vec()), expected);
+                } else {
+                    let old = table.remove(key).unwrap();
+                    let expected = reference.remove(&key);
+                    assert_eq!(old.map(|x| x.value().to_vec()), expected);
+                }
+                let key = rng.gen_range(0..1000u64);
+                let value = table.get(key).unwrap().map(|x| x.value().to_vec());
+                assert_eq!(value.as_ref(), reference.get(&key));
+            }
+        }
+        write_txn.commit().unwrap();
+    }
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let contents: Vec<(u64, Vec<u8>)> = table
+        .iter()
+        .unwrap()
+        .map(|x| {
+            let (key, value) = x.unwrap();
+            (key.value(), value.value().to_vec())
+        })
+        .collect();
+    assert_eq!(contents, reference.into_iter().collect::<Vec<_>>());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_traversal() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..100_000u64 {
+            table
+                .insert(&i.wrapping_mul(0x9E37_79B9_7F4A_7C15), &i)
+                .unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    table.verify_structure().unwrap();
+    let stats = table.stats().unwrap();
+    let levels = stats.pages_per_level().to_vec();
+    assert!(levels.len() > 2);
+    assert_eq!(levels.len(), stats.tree_height() as usize);
+    assert_eq!(
+        levels.iter().sum::<u64>(),
+        stats.leaf_pages() + stats.branch_pages()
+    );
+    let write_txn = db.begin_write().unwrap();
+    let db_stats = write_txn.stats().unwrap();
+    assert_eq!(db_stats.leaf_pages(), *levels.last().unwrap());
+    write_txn.abort().unwrap();
+}
+
+#[test]
+fn drain_entire_table() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let fill = |db: &Database| {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            for i in 0..10_000u64 {
+                table.insert(&i, &(i * 2)).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+    };
+
+    fill(&db);
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let entries: Vec<(u64, u64)> = table
+            .drain::<u64>(..)
+            .unwrap()
+            .map(|x| {
+                let (k, v) = x.unwrap();
+                (k.value(), v.value())
+            })
+            .collect();
+        assert_eq!(entries, (0..10_000).map(|i| (i, i * 2)).collect::<Vec<_>>());
+        assert!(table.is_empty().unwrap());
+    }
+    assert_eq!(write_txn.stats().unwrap().leaf_pages(), 0);
+    write_txn.commit().unwrap();
+
+    // Dropping the iterator early still removes every entry
+    fill(&db);
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let mut iter = table.drain::<u64>(..).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().0.value(), 0);
+        assert_eq!(iter.next_back().unwrap().unwrap().0.value(), 9_999);
+        drop(iter);
+        assert!(table.is_empty().unwrap());
+        assert!(table.first().unwrap().is_none());
+        table.insert(&1, &1).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 1);
+}
+
+#[test]
+fn repeated_insert_remove_does_not_leak() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let cycle = |db: &Database, iterations: u64| {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            for i in 0..iterations {
+                table.insert(&500_000, &i).unwrap();
+                assert_eq!(table.remove(&500_000).unwrap().unwrap().value(), i);
+            }
+        }
+        write_txn.commit().unwrap();
+    };
+    let allocated_pages = |db: &Database| {
+        // Extra commits to finalize the cleanup of the freed pages
+        for _ in 0..2 {
+            db.begin_write().unwrap().commit().unwrap();
+        }
+        let write_txn = db.begin_write().unwrap();
+        let pages = write_txn.stats().unwrap().allocated_pages();
+        write_txn.abort().unwrap();
+        pages
+    };
+
+    // The first insertion splits the last leaf, and removing the key again doesn't merge it back
+    cycle(&db, 1);
+    let baseline = allocated_pages(&db);
+    for _ in 0..10 {
+        cycle(&db, 10_000);
+    }
+    assert_eq!(allocated_pages(&db), baseline);
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 1000);
+}
+
+#[test]
+fn reserve_pages() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let file_len = || std::fs::metadata(tmpfile.path()).unwrap().len();
+
+    let write_txn = db.begin_write().unwrap();
+    let before = file_len();
+    // Each insert allocates about one page, plus a few for the branch pages
+    write_txn.reserve_pages(2000).unwrap();
+    let reserved = file_len();
+    let page_size = write_txn.stats().unwrap().page_size() as u64;
+    assert!(reserved > before);
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        let value = vec![0u8; page_size as usize / 2];
+        for i in 0..1000u64 {
+            table
+                .insert(i.to_be_bytes().as_slice(), value.as_slice())
+                .unwrap();
+        }
+    }
+    assert!(write_txn.stats().unwrap().allocated_pages() >= 500);
+    assert_eq!(file_len(), reserved);
+    write_txn.commit().unwrap();
+
+    // Reserving pages which are already free doesn't grow the file
+    let write_txn = db.begin_write().unwrap();
+    let len = file_len();
+    write_txn.reserve_pages(1).unwrap();
+    assert_eq!(file_len(), len);
+    // More pages than the database can hold are rejected, without growing the file
+    assert!(write_txn.reserve_pages(u64::MAX).is_err());
+    assert!(write_txn.reserve_pages(u64::MAX / page_size).is_err());
+    assert_eq!(file_len(), len);
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"key".as_slice(), b"value".as_slice()).unwrap();
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn deterministic_layout() {
+    let build = || {
+        let tmpfile = create_tempfile();
+        let db = Database::create(tmpfile.path()).unwrap();
+        for round in 0..5u64 {
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(U64_TABLE).unwrap();
+                for i in 0..1000u64 {
+                    table.insert(&(i * 7 % 1000 + round * 500), &i).unwrap();
+                }
+                table.remove_range(round * 100..round * 300).unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+        drop(db);
+        std::fs::read(tmpfile.path()).unwrap()
+    };
+
+    // Page numbers only depend on the sequence of operations, so the files are identical
+    let first = build();
+    let second = build();
+    assert_eq!(first.len(), second.len());
+    assert!(first == second);
+}
+
+#[test]
+fn drain_filter() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10 {
+            table.insert(&i, &i).unwrap();
+        }
+        // Test draining uncommitted data
+        drop(table.drain_filter(0..10, |k, _| k < 5).unwrap());
+        for i in 0..5 {
+            table.insert(&i, &i).unwrap();
+        }
+        assert_eq!(table.len().unwrap(), 10);
+
+        // Test matching on the value
+        drop(table.drain_filter(0..10, |_, v| v < 5).unwrap());
+        for i in 0..5 {
+            table.insert(&i, &i).unwrap();
+        }
+        assert_eq!(table.len().unwrap(), 10);
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 10);
+        for (i, item) in table.drain_filter(0.., |x, _| x < 5).unwrap().enumerate() {
+            let (k, v) = item.unwrap();
+            assert_eq!(i as u64, k.value());
+            assert_eq!(i as u64, v.value());
+        }
+        assert_eq!(table.len().unwrap(), 5);
+        let mut i = 5u64;
+        for item in table.range(0..10).unwrap() {
+            let (k, v) = item.unwrap();
+            assert_eq!(i, k.value());
+            assert_eq!(i, v.value());
+            i += 1;
+        }
+    }
+    write_txn.abort().unwrap();
+
+    // Check that dropping the iter early works too
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 10);
+        drop(table.drain_filter(0.., |x, _| x < 5).unwrap());
+        assert_eq!(table.len().unwrap(), 5);
+    }
+    write_txn.abort().unwrap();
+}
+
+#[test]
+fn stored_size() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    assert_eq!(write_txn.stats().unwrap().stored_bytes(), 10);
+    assert!(write_txn.stats().unwrap().fragmented_bytes() > 0);
+    assert!(write_txn.stats().unwrap().metadata_bytes() > 0);
+    write_txn.abort().unwrap();
+}
+
+#[test]
+fn create_open() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.insert(&0, &1).unwrap();
+    }
+    write_txn.commit().unwrap();
+    drop(db);
+
+    let db2 = Database::open(tmpfile.path()).unwrap();
+
+    let read_txn = db2.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(1, table.get(&0).unwrap().unwrap().value());
+}
+
+#[test]
+fn multiple_tables() {
+    let definition1: TableDefinition<&str, &str> = TableDefinition::new("1");
+    let definition2: TableDefinition<&str, &str> = TableDefinition::new("2");
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition1).unwrap();
+        let mut table2 = write_txn.open_table(definition2).unwrap();
+
+        table.insert("hello", "world").unwrap();
+        table2.insert("hello", "world2").unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition1).unwrap();
+    let table2 = read_txn.open_table(definition2).unwrap();
+    assert_eq!(table.len().unwrap(), 1);
+    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
+    assert_eq!(table2.len().unwrap(), 1);
+    assert_eq!("world2", table2.get("hello").unwrap().unwrap().value());
+}
+
+#[test]
+fn list_tables() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let definition_x: TableDefinition<&[u8], &[u8]> = TableDefinition::new("x");
+    let definition_y: TableDefinition<&[u8], &[u8]> = TableDefinition::new("y");
+    let definition_mx: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("mx");
+    let definition_my: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("my");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        write_txn.open_table(definition_x).unwrap();
+        write_txn.open_table(definition_y).unwrap();
+        write_txn.open_multimap_table(definition_mx).unwrap();
+        write_txn.open_multimap_table(definition_my).unwrap();
+    }
+
+    let tables: Vec<String> = write_txn
+        .list_tables()
+        .unwrap()
+        .map(|h| h.name().to_string())
+        .collect();
+    let multimap_tables: Vec<String> = write_txn
+        .list_multimap_tables()
+        .unwrap()
+        .map(|h| h.name().to_string())
+        .collect();
+    assert_eq!(tables, &["x", "y"]);
+    assert_eq!(multimap_tables, &["mx", "my"]);
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let tables: Vec<String> = read_txn
+        .list_tables()
+        .unwrap()
+        .map(|h| h.name().to_string())
+        .collect();
+    let multimap_tables: Vec<String> = read_txn
+        .list_multimap_tables()
+        .unwrap()
+        .map(|h| h.name().to_string())
+        .collect();
+    assert_eq!(tables, &["x", "y"]);
+    assert_eq!(multimap_tables, &["mx", "my"]);
+}
+
+#[test]
+// Test that these signatures compile
+fn tuple_type_function_lifetime() {
+    #[allow(dead_code)]
+    fn insert_inferred_lifetime(table: &mut redb::Table<(&str, u8), u64>) {
+        table
+            .insert(&(String::from("hello").as_str(), 8), &1)
+            .unwrap();
+    }
+
+    #[allow(dead_code)]
+    #[allow(clippy::needless_lifetimes)]
+    fn insert_explicit_lifetime<'a>(table: &mut redb::Table<(&'a str, u8), u64>) {
+        table
+            .insert(&(String::from("hello").as_str(), 8), &1)
+            .unwrap();
+    }
+}
+
+#[test]
+fn tuple_type_lifetime() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8), (u16, u32)> = TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table
+            .insert(&(String::from("hello").as_str(), 5), &(0, 123))
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(table.get(&("hello", 5)).unwrap().unwrap().value(), (0, 123));
+}
+
+#[test]
+fn tuple2_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8), (u16, u32)> = TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table.insert(&("hello", 5), &(0, 123)).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(table.get(&("hello", 5)).unwrap().unwrap().value(), (0, 123));
+}
+
+#[test]
+fn tuple3_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16), (u16, u32)> = TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table.insert(&("hello", 5, 6), &(0, 123)).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table.get(&("hello", 5, 6)).unwrap().unwrap().value(),
+        (0, 123)
+    );
+}
+
+#[test]
+fn tuple4_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16, u32), (u16, u32)> =
+        TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table.insert(&("hello", 5, 6, 7), &(0, 123)).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table.get(&("hello", 5, 6, 7)).unwrap().unwrap().value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple5_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16, u32, u64), (u16, u32)> =
+        TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table.insert(&("hello", 5, 6, 7, 8), &(0, 123)).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table.get(&("hello", 5, 6, 7, 8)).unwrap().unwrap().value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple6_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128), (u16, u32)> =
+        TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table.insert(&("hello", 5, 6, 7, 8, 9), &(0, 123)).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table
+            .get(&("hello", 5, 6, 7, 8, 9))
+            .unwrap()
+            .unwrap()
+            .value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple7_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8), (u16, u32)> =
+        TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table
+            .insert(&("hello", 5, 6, 7, 8, 9, -1), &(0, 123))
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table
+            .get(&("hello", 5, 6, 7, 8, 9, -1))
+            .unwrap()
+            .unwrap()
+            .value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple8_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8, i16), (u16, u32)> =
+        TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table
+            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2), &(0, 123))
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table
+            .get(&("hello", 5, 6, 7, 8, 9, -1, -2))
+            .unwrap()
+            .unwrap()
+            .value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple9_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8, i16, i32), (u16, u32)> =
+        TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table
+            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2, -3), &(0, 123))
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table
+            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3))
+            .unwrap()
+            .unwrap()
+            .value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple10_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8, i16, i32, i64), (u16, u32)> =
+        TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table
+            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4), &(0, 123))
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table
+            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4))
+            .unwrap()
+            .unwrap()
+            .value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple11_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<
+        (&str, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128),
+        (u16, u32),
+    > = TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table
+            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5), &(0, 123))
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table
+            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5))
+            .unwrap()
+            .unwrap()
+            .value(),
+        (0, 123)
+    );
+}
+
+#[test]
+#[allow(clippy::type_complexity)]
+fn tuple12_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let table_def: TableDefinition<
+        (&str, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, &str),
+        (u16, u32),
+    > = TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table
+            .insert(
+                &("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5, "end"),
+                &(0, 123),
+            )
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table
+            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5, "end"))
+            .unwrap()
+            .unwrap()
+            .value(),
+        (0, 123)
+    );
+}
+
+// Run-length encoding, as a simple codec with no dependencies
+#[derive(Debug)]
+struct RunLength;
+
+impl redb::Codec for RunLength {
+    fn compress(data: &[u8]) -> Option<Vec<u8>> {
+        let mut result: Vec<u8> = vec![];
+        for &byte in data {
+            match result.len().checked_sub(2) {
+                Some(last) if result[last] < u8::MAX && result[last + 1] == byte => {
+                    result[last] += 1;
+                }
+                _ => result.extend_from_slice(&[1, byte]),
+            }
+        }
+        // Data without runs doubles in size, so store it as is
+        if result.len() > data.len() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    fn decompress(data: &[u8], uncompressed_len: usize) -> Option<Vec<u8>> {
+        if data.len() % 2 != 0 {
+            return None;
+        }
+        let mut result = Vec::with_capacity(uncompressed_len.min(data.len() / 2 * 255));
+        for pair in data.chunks(2) {
+            result.extend(std::iter::repeat(pair[1]).take(pair[0] as usize));
+        }
+        Some(result)
+    }
+
+    fn name() -> &'static str {
+        "run_length"
+    }
+}
+
+fn compressed_round_trip<C: redb::Codec>() {
+    use rand::prelude::{Rng, SeedableRng, StdRng};
+    use redb::Compressed;
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let bytes_def: TableDefinition<u64, Compressed<C, Vec<u8>>> = TableDefinition::new("bytes");
+    let string_def: TableDefinition<u64, Compressed<C, String>> = TableDefinition::new("string");
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut random = vec![0u8; 10_000];
+    rng.fill(random.as_mut_slice());
+    let values = vec![
+        vec![],
+        vec![7],
+        vec![0; 10_000],
+        "ab".repeat(5000).into_bytes(),
+        random,
+    ];
+    let text = "the quick brown fox jumps over the lazy dog ".repeat(100);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut bytes_table = write_txn.open_table(bytes_def).unwrap();
+        for (i, value) in values.iter().enumerate() {
+            bytes_table.insert(i as u64, value).unwrap();
+        }
+        let mut string_table = write_txn.open_table(string_def).unwrap();
+        string_table.insert(0, &text).unwrap();
+        string_table.insert(1, &String::new()).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let bytes_table = read_txn.open_table(bytes_def).unwrap();
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(&bytes_table.get(i as u64).unwrap().unwrap().value(), value);
+    }
+    let string_table = read_txn.open_table(string_def).unwrap();
+    assert_eq!(string_table.get(0).unwrap().unwrap().value(), text);
+    assert_eq!(string_table.get(1).unwrap().unwrap().value(), "");
+
+    // Values which don't compress are stored with a single byte of overhead
+    for value in &values {
+        let encoded = <Compressed<C, Vec<u8>>>::as_bytes(value);
+        assert!(encoded.len() <= <Vec<u8>>::as_bytes(value).len() + 1);
+        assert_eq!(<Compressed<C, Vec<u8>>>::from_bytes(&encoded), *value);
+    }
+    let zeros = <Compressed<C, Vec<u8>>>::as_bytes(&values[2]);
+    assert!(zeros.len() < 1000, "{}", zeros.len());
+    let random = <Compressed<C, Vec<u8>>>::as_bytes(&values[4]);
+    assert_eq!(random.len(), <Vec<u8>>::as_bytes(&values[4]).len() + 1);
+
+    // Invalid data is reported as corruption, including a length too large to allocate
+    for invalid in [
+        &[][..],
+        &[2, 0],
+        &[1, 0, 0],
+        &[1, 0xff, 0xff, 0xff, 0xff, 1, 7],
+    ] {
+        assert!(matches!(
+            <Compressed<C, Vec<u8>>>::try_from_bytes(invalid),
+            Err(StorageError::Corrupted(_))
+        ));
+    }
+    // A corrupt length which is within the maximum, but far longer than the data decompresses to
+    let mut wrong_len = zeros.clone();
+    wrong_len[1..5].copy_from_slice(&(redb::MAX_VALUE_LENGTH as u32).to_le_bytes());
+    assert!(matches!(
+        <Compressed<C, Vec<u8>>>::try_from_bytes(&wrong_len),
+        Err(StorageError::Corrupted(_))
+    ));
+}
+
+#[test]
+fn compressed_type() {
+    compressed_round_trip::<RunLength>();
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn compressed_lz4() {
+    compressed_round_trip::<redb::Lz4>();
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn compressed_zstd() {
+    compressed_round_trip::<redb::Zstd>();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn bincode_type() {
+    use redb::{Bincode, BincodeValue};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Inner {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Outer {
+        id: u64,
+        inner: Inner,
+        parent: Option<Box<Outer>>,
+    }
+
+    impl BincodeValue for Outer {
+        const NAME: &'static str = "test::Outer";
+    }
+
+    let value = Outer {
+        id: 2,
+        inner: Inner {
+            name: "child".to_string(),
+            tags: vec!["a".to_string(), "b".to_string()],
+        },
+        parent: Some(Box::new(Outer {
+            id: 1,
+            inner: Inner {
+                name: "parent".to_string(),
+                tags: vec![],
+            },
+            parent: None,
+        })),
+    };
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let table_def: TableDefinition<u64, Bincode<Outer>> = TableDefinition::new("table");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        table.insert(&2, &value).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(table.get(&2).unwrap().unwrap().value(), value);
+    assert!(table.get(&1).unwrap().is_none());
+
+    // A truncated value is reported as corrupted, rather than panicking
+    let bytes = <Bincode<Outer>>::as_bytes(&value);
+    assert!(matches!(
+        <Bincode<Outer>>::try_from_bytes(&bytes[..bytes.len() - 1]),
+        Err(StorageError::Corrupted(_))
+    ));
+    // So is a length prefix longer than the data. The name's length follows the id
+    let mut huge = bytes.clone();
+    huge[8..16].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    assert!(matches!(
+        <Bincode<Outer>>::try_from_bytes(&huge),
+        Err(StorageError::Corrupted(_))
+    ));
+}
+
+#[test]
+fn try_from_bytes() {
+    assert_eq!(u64::try_from_bytes(&5u64.to_le_bytes()).unwrap(), 5);
+    assert!(u64::try_from_bytes(&[1, 2, 3]).is_err());
+    assert!(i32::try_from_bytes(&[1, 2, 3, 4, 5]).is_err());
+    assert!(f64::try_from_bytes(&[]).is_err());
+
+    assert_eq!(<&str>::try_from_bytes(b"hello").unwrap(), "hello");
+    assert!(<&str>::try_from_bytes(&[0xff, 0xfe]).is_err());
+    // Truncated multi-byte character
+    assert!(<&str>::try_from_bytes(&"é".as_bytes()[..1]).is_err());
+    assert_eq!(String::try_from_bytes(b"hello").unwrap(), "hello");
+    assert!(String::try_from_bytes(&[0xff, 0xfe]).is_err());
+
+    assert_eq!(<&[u8; 2]>::try_from_bytes(&[1, 2]).unwrap(), &[1, 2]);
+    assert!(<&[u8; 2]>::try_from_bytes(&[1]).is_err());
+    assert!(<()>::try_from_bytes(&[1]).is_err());
+    assert!(bool::try_from_bytes(&[1]).unwrap());
+    assert!(bool::try_from_bytes(&[2]).is_err());
+    assert!(bool::try_from_bytes(&[]).is_err());
+
+    assert_eq!(
+        Vec::<u16>::try_from_bytes(&[2, 0, 0, 0, 1, 0, 2, 0]).unwrap(),
+        vec![1, 2]
+    );
+    // Element bytes don't match the number of elements
+    assert!(Vec::<u16>::try_from_bytes(&[2, 0, 0, 0, 1, 0, 2]).is_err());
+    assert!(Vec::<u16>::try_from_bytes(&[1, 0, 0, 0, 1, 0, 2, 0]).is_err());
+    assert!(Vec::<u16>::try_from_bytes(&[1, 0, 0]).is_err());
+    // Element length beyond the end of the data
+    assert!(Vec::<&str>::try_from_bytes(&[1, 0, 0, 0, 5, 0, 0, 0, b'a']).is_err());
+
+    assert_eq!(Option::<u8>::try_from_bytes(&[0]).unwrap(), None);
+    assert_eq!(Option::<u8>::try_from_bytes(&[1, 7]).unwrap(), Some(7));
+    assert!(Option::<u8>::try_from_bytes(&[]).is_err());
+    assert!(Option::<u8>::try_from_bytes(&[2, 7]).is_err());
+    assert!(Option::<u16>::try_from_bytes(&[1, 7]).is_err());
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    let guard = table.get("hello").unwrap().unwrap();
+    assert_eq!(guard.try_value().unwrap(), "world");
+}
+
+#[test]
+fn empty_key_and_value() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        assert!(table.get(b"".as_slice()).unwrap().is_none());
+        table.insert(b"".as_slice(), b"".as_slice()).unwrap();
+        table.insert(b"\0".as_slice(), b"zero".as_slice()).unwrap();
+        table.insert(b"a".as_slice(), b"".as_slice()).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 3);
+    let empty: &[u8] = b"";
+    assert_eq!(table.get(empty).unwrap().unwrap().value(), empty);
+    assert_eq!(table.get(b"a".as_slice()).unwrap().unwrap().value(), empty);
+    assert!(table.get(b"b".as_slice()).unwrap().is_none());
+    let keys: Vec<Vec<u8>> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value().to_vec())
+        .collect();
+    assert_eq!(keys, vec![b"".to_vec(), b"\0".to_vec(), b"a".to_vec()]);
+    drop(table);
+    drop(read_txn);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        assert_eq!(table.remove(empty).unwrap().unwrap().value(), empty);
+        assert!(table.remove(empty).unwrap().is_none());
+        assert_eq!(table.len().unwrap(), 2);
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn empty_key_separator() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let empty: &[u8] = b"";
+    let large_value = vec![1u8; 3500];
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        // The large value fills the first leaf, so that the empty key becomes the separator
+        // between it and the next one
+        table.insert(empty, large_value.as_slice()).unwrap();
+        for i in 0..200u32 {
+            let key = format!("k{i:03}");
+            table.insert(key.as_bytes(), [2u8; 500].as_slice()).unwrap();
+        }
+        table.verify_structure().unwrap();
+    }
+    write_txn.commit().unwrap();
+    drop(db);
+
+    let db = Database::open(tmpfile.path()).unwrap();
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    table.verify_structure().unwrap();
+    assert_eq!(table.len().unwrap(), 201);
+    assert_eq!(table.get(empty).unwrap().unwrap().value(), large_value);
+    assert_eq!(table.first().unwrap().unwrap().0.value(), empty);
+    let after_empty = table.range::<&[u8]>((Bound::Excluded(empty), Bound::Unbounded));
+    assert_eq!(
+        after_empty.unwrap().next().unwrap().unwrap().0.value(),
+        b"k000"
+    );
+    assert_eq!(table.range(..=empty).unwrap().count(), 1);
+    drop(table);
+    drop(read_txn);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        assert!(table.remove(empty).unwrap().is_some());
+        assert!(table.get(empty).unwrap().is_none());
+        table.verify_structure().unwrap();
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn bool_type() {
+    let definition: TableDefinition<bool, bool> = TableDefinition::new("x");
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table.insert(true, false).unwrap();
+        table.insert(false, true).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert!(!table.get(true).unwrap().unwrap().value());
+    assert!(table.get(false).unwrap().unwrap().value());
+    let keys: Vec<bool> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value())
+        .collect();
+    assert_eq!(keys, vec![false, true]);
+}
+
+#[test]
+fn float_keys() {
+    let definition: TableDefinition<f64, f32> = TableDefinition::new("x");
+    let keys = [
+        f64::NAN,
+        1.5,
+        f64::INFINITY,
+        -0.0,
+        f64::NEG_INFINITY,
+        0.0,
+        -1.5,
+        f64::MIN_POSITIVE,
+    ];
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            table.insert(key, i as f32 / 2.0).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(table.len().unwrap(), keys.len() as u64);
+    assert_eq!(table.get(1.5).unwrap().unwrap().value(), 0.5);
+    assert_eq!(table.get(f64::NAN).unwrap().unwrap().value(), 0.0);
+    // Zeros with different signs are distinct keys
+    assert_eq!(table.get(-0.0).unwrap().unwrap().value(), 1.5);
+    assert_eq!(table.get(0.0).unwrap().unwrap().value(), 2.5);
+
+    let stored: Vec<u64> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value().to_bits())
+        .collect();
+    let expected: Vec<u64> = [
+        f64::NEG_INFINITY,
+        -1.5,
+        -0.0,
+        0.0,
+        f64::MIN_POSITIVE,
+        1.5,
+        f64::INFINITY,
+        f64::NAN,
+    ]
+    .iter()
+    .map(|x| x.to_bits())
+    .collect();
+    assert_eq!(stored, expected);
+}
+
+#[test]
+fn ip_addr_keys() {
+    let definition: TableDefinition<IpAddr, u64> = TableDefinition::new("x");
+    let addresses: Vec<IpAddr> = [
+        "::1",
+        "10.0.0.1",
+        "2001:db8::1",
+        "192.168.1.1",
+        "9.255.255.255",
+        "::",
+        "255.255.255.255",
+        "0.0.0.0",
+        "fe80::1",
+    ]
+    .iter()
+    .map(|x| x.parse().unwrap())
+    .collect();
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for (i, addr) in addresses.iter().enumerate() {
+            table.insert(addr, &(i as u64)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let v6_loopback: IpAddr = "::1".parse().unwrap();
+    assert_eq!(table.get(&v6_loopback).unwrap().unwrap().value(), 0);
+
+    // IPv4 addresses sort before IPv6 addresses, and each family is in numeric order
+    let stored: Vec<IpAddr> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value())
+        .collect();
+    let mut expected = addresses.clone();
+    expected.sort();
+    assert_eq!(stored, expected);
+    assert!(stored[..5].iter().all(|x| x.is_ipv4()));
+
+    let v4_definition: TableDefinition<Ipv4Addr, ()> = TableDefinition::new("v4");
+    let v6_definition: TableDefinition<Ipv6Addr, ()> = TableDefinition::new("v6");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut v4_table = write_txn.open_table(v4_definition).unwrap();
+        let mut v6_table = write_txn.open_table(v6_definition).unwrap();
+        for addr in &addresses {
+            match addr {
+                IpAddr::V4(addr) => v4_table.insert(addr, ()).unwrap(),
+                IpAddr::V6(addr) => v6_table.insert(addr, ()).unwrap(),
+            };
+        }
+        let v4: Vec<IpAddr> = v4_table
+            .iter()
+            .unwrap()
+            .map(|x| IpAddr::V4(x.unwrap().0.value()))
+            .collect();
+        let v6: Vec<IpAddr> = v6_table
+            .iter()
+            .unwrap()
+            .map(|x| IpAddr::V6(x.unwrap().0.value()))
+            .collect();
+        assert_eq!([v4, v6].concat(), expected);
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn owned_array_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<[u8; 16], u64> = TableDefinition::new("x");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in [5u128, 1, u128::MAX, 0, 1 << 64, 3] {
+            table
+                .insert(&i.to_be_bytes(), &u64::try_from(i % 100).unwrap())
+                .unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let keys: Vec<u128> = table
+        .iter()
+        .unwrap()
+        .map(|x| u128::from_be_bytes(x.unwrap().0.value()))
+        .collect();
+    assert_eq!(keys, vec![0, 1, 3, 5, 1 << 64, u128::MAX]);
+    assert_eq!(table.get(&5u128.to_be_bytes()).unwrap().unwrap().value(), 5);
+    assert!(table.get(&[0xFF; 16]).unwrap().is_some());
+    assert!(table.get(&[0xFE; 16]).unwrap().is_none());
+
+    // Owned and borrowed arrays are distinct types
+    let borrowed: TableDefinition<&[u8; 16], u64> = TableDefinition::new("x");
+    assert!(matches!(
+        read_txn.open_table(borrowed),
+        Err(TableError::TableTypeMismatch { .. })
+    ));
+}
+
+#[test]
+fn is_empty() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        assert!(table.is_empty().unwrap());
+        table.insert("hello", "world").unwrap();
+        assert!(!table.is_empty().unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    assert!(!table.is_empty().unwrap());
+}
+
+#[test]
+fn abort() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "aborted").unwrap();
+        assert_eq!("aborted", table.get("hello").unwrap().unwrap().value());
+    }
+    write_txn.abort().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE);
+    assert!(table.is_err());
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
+    assert_eq!(table.len().unwrap(), 1);
+}
+
+#[test]
+fn insert_overwrite() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        assert!(table.insert("hello", "world").unwrap().is_none());
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        let old_value = table.insert("hello", "replaced").unwrap();
+        assert_eq!(old_value.unwrap().value(), "world");
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    assert_eq!("replaced", table.get("hello").unwrap().unwrap().value());
+}
+
+#[test]
+fn insert_returns_old_value() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let value = |i: u64, round: usize| vec![i as u8; (i as usize % 7) * 300 * round];
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for i in 0..500u64 {
+            let old = table.insert(i.to_be_bytes().as_slice(), value(i, 1).as_slice());
+            assert!(old.unwrap().is_none());
+        }
+        // Overwrite uncommitted pages, alternately growing and shrinking the values, which
+        // exercises the in-place, rebuild and split paths
+        let mut previous = 1;
+        for round in [3, 0, 2] {
+            for i in 0..500u64 {
+                let new_value = value(i, round);
+                let old = table
+                    .insert(i.to_be_bytes().as_slice(), new_value.as_slice())
+                    .unwrap();
+                assert_eq!(old.unwrap().value(), value(i, previous).as_slice());
+            }
+            previous = round;
+        }
+    }
+    write_txn.commit().unwrap();
+
+    // Overwrite committed pages
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        for i in 0..500u64 {
+            let old = table.insert(i.to_be_bytes().as_slice(), b"x".as_slice());
+            assert_eq!(old.unwrap().unwrap().value(), value(i, 2).as_slice());
+        }
+        table.verify_structure().unwrap();
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn slice_values_borrow_page_memory() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        table
+            .insert(b"hello".as_slice(), b"world".as_slice())
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    let guard = table.get(b"hello".as_slice()).unwrap().unwrap();
+    let (_, iter_guard) = table.iter().unwrap().next().unwrap().unwrap();
+    // Both guards decode the value directly from the same cached page, without copying it
+    assert_eq!(guard.value(), b"world");
+    assert_eq!(guard.value().as_ptr(), iter_guard.value().as_ptr());
+}
+
+#[test]
+fn overwrite_separator_keys() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..2000u64 {
+            table.insert(i, [0u8; 100].as_slice()).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    // The last key of every leaf is also stored as a separator in its parent. Overwrite every key
+    // repeatedly, both within a transaction and across them, with values that change the leaf
+    // layout, and check that each key is still stored exactly once
+    for round in 1..=4u8 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(definition).unwrap();
+            for _ in 0..2 {
+                for i in 0..2000u64 {
+                    let value = vec![round; (i as usize * round as usize) % 700];
+                    table.insert(i, value.as_slice()).unwrap();
+                }
+            }
+            table.verify_structure().unwrap();
+            assert_eq!(table.len().unwrap(), 2000);
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(definition).unwrap();
+        for (i, entry) in table.iter().unwrap().enumerate() {
+            let (key, value) = entry.unwrap();
+            let i = i as u64;
+            assert_eq!(key.value(), i);
+            assert_eq!(
+                value.value(),
+                vec![round; (i as usize * round as usize) % 700].as_slice()
+            );
+        }
+        assert_eq!(table.len().unwrap(), 2000);
+    }
+}
+
+#[test]
+fn floor_ceiling() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        // Enough even keys to span many leaves
+        for i in 0..5000u64 {
+            table.insert(&(i * 2 + 10), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let ceiling = |key: u64| {
+        table
+            .range(key..)
+            .unwrap()
+            .next()
+            .map(|x| x.unwrap().0.value())
+    };
+    let floor = |key: u64| {
+        table
+            .range(..=key)
+            .unwrap()
+            .next_back()
+            .map(|x| x.unwrap().0.value())
+    };
+    for key in (11..10_008).step_by(2) {
+        assert_eq!(ceiling(key), Some(key + 1));
+        assert_eq!(floor(key), Some(key - 1));
+    }
+    assert_eq!(ceiling(100), Some(100));
+    assert_eq!(floor(100), Some(100));
+    // Beyond the ends of the table
+    assert_eq!(ceiling(0), Some(10));
+    assert_eq!(floor(0), None);
+    assert_eq!(ceiling(10_009), None);
+    assert_eq!(floor(u64::MAX), Some(10_008));
+}
+
+#[test]
+fn successor_predecessor() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..5000u64 {
+            table.insert(&(i * 2 + 10), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let successor = |key: u64| {
+        table
+            .range::<u64>((Bound::Excluded(key), Bound::Unbounded))
+            .unwrap()
+            .next()
+            .map(|x| x.unwrap().0.value())
+    };
+    let predecessor = |key: u64| {
+        table
+            .range::<u64>((Bound::Unbounded, Bound::Excluded(key)))
+            .unwrap()
+            .next_back()
+            .map(|x| x.unwrap().0.value())
+    };
+    // Every present key, so that the neighbor is sometimes in the same leaf, and sometimes in the
+    // adjacent one
+    for key in (12..10_008).step_by(2) {
+        assert_eq!(successor(key), Some(key + 2));
+        assert_eq!(predecessor(key), Some(key - 2));
+        assert_eq!(successor(key - 1), Some(key));
+        assert_eq!(predecessor(key + 1), Some(key));
+    }
+    assert_eq!(successor(10_008), None);
+    assert_eq!(predecessor(10), None);
+    assert_eq!(successor(0), Some(10));
+    assert_eq!(predecessor(u64::MAX), Some(10_008));
+}
+
+#[test]
+fn first_last() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        assert!(table.first().unwrap().is_none());
+        assert!(table.last().unwrap().is_none());
+        table.insert("b", "2").unwrap();
+        {
+            let (key, value) = table.first().unwrap().unwrap();
+            assert_eq!(key.value(), "b");
+            assert_eq!(value.value(), "2");
+            let (key, _) = table.last().unwrap().unwrap();
+            assert_eq!(key.value(), "b");
+        }
+        table.insert("a", "1").unwrap();
+        table.insert("c", "3").unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    let (key, value) = table.first().unwrap().unwrap();
+    assert_eq!(key.value(), "a");
+    assert_eq!(value.value(), "1");
+    let (key, value) = table.last().unwrap().unwrap();
+    assert_eq!(key.value(), "c");
+    assert_eq!(value.value(), "3");
+}
+
+#[test]
+fn first_last_in_range() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert!(table.first_in_range(0..10).unwrap().is_none());
+        // Even keys, spread over many leaves
+        for i in 0..5000u64 {
+            table.insert(2 * i, i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let first = |range: (Bound<u64>, Bound<u64>)| {
+        table
+            .first_in_range(range)
+            .unwrap()
+            .map(|(k, v)| (k.value(), v.value()))
+    };
+    let last = |range: (Bound<u64>, Bound<u64>)| {
+        table
+            .last_in_range(range)
+            .unwrap()
+            .map(|(k, v)| (k.value(), v.value()))
+    };
+
+    // Endpoints which fall between the keys of a leaf
+    assert_eq!(
+        first((Bound::Included(1001), Bound::Excluded(3001))),
+        Some((1002, 501))
+    );
+    assert_eq!(
+        last((Bound::Included(1001), Bound::Excluded(3001))),
+        Some((3000, 1500))
+    );
+    // Endpoints on existing keys
+    assert_eq!(
+        first((Bound::Excluded(1000), Bound::Included(3000))),
+        Some((1002, 501))
+    );
+    assert_eq!(
+        last((Bound::Excluded(1000), Bound::Included(3000))),
+        Some((3000, 1500))
+    );
+    assert_eq!(
+        last((Bound::Excluded(1000), Bound::Excluded(3000))),
+        Some((2998, 1499))
+    );
+    // Unbounded ends match first() and last()
+    assert_eq!(first((Bound::Unbounded, Bound::Included(5))), Some((0, 0)));
+    assert_eq!(
+        last((Bound::Included(5), Bound::Unbounded)),
+        Some((9998, 4999))
+    );
+    // Ranges which contain no keys
+    assert_eq!(first((Bound::Included(7), Bound::Excluded(8))), None);
+    assert_eq!(last((Bound::Included(7), Bound::Excluded(8))), None);
+    assert_eq!(first((Bound::Excluded(9998), Bound::Unbounded)), None);
+
+    // Agrees with the ends of the full range, for ranges of every size
+    for (start, end) in [
+        (0, 1),
+        (3, 4),
+        (3, 5),
+        (999, 1003),
+        (4095, 8191),
+        (9990, 10_000),
+    ] {
+        let entries: Vec<u64> = table
+            .range(start..end)
+            .unwrap()
+            .map(|x| x.unwrap().0.value())
+            .collect();
+        assert_eq!(
+            table
+                .first_in_range(start..end)
+                .unwrap()
+                .map(|x| x.0.value()),
+            entries.first().copied()
+        );
+        assert_eq!(
+            table
+                .last_in_range(start..end)
+                .unwrap()
+                .map(|x| x.0.value()),
+            entries.last().copied()
+        );
+    }
+}
+
+#[test]
+fn is_empty_adjacent_tables() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let empty_definition: TableDefinition<u64, u64> = TableDefinition::new("empty");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            table.insert(&i, &i).unwrap();
+        }
+        assert!(!table.is_empty().unwrap());
+        let table = write_txn.open_table(empty_definition).unwrap();
+        assert!(table.is_empty().unwrap());
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"a".as_slice(), b"b".as_slice()).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    assert!(!read_txn.open_table(U64_TABLE).unwrap().is_empty().unwrap());
+    assert!(read_txn
+        .open_table(empty_definition)
+        .unwrap()
+        .is_empty()
+        .unwrap());
+    assert!(!read_txn
+        .open_table(SLICE_TABLE)
+        .unwrap()
+        .is_empty()
+        .unwrap());
+    drop(read_txn);
+
+    // Removing every entry, one at a time, leaves the table empty
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            assert!(!table.is_empty().unwrap());
+            table.remove(&i).unwrap();
+        }
+        assert!(table.is_empty().unwrap());
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    assert!(read_txn.open_table(U64_TABLE).unwrap().is_empty().unwrap());
+    assert!(!read_txn
+        .open_table(SLICE_TABLE)
+        .unwrap()
+        .is_empty()
+        .unwrap());
+}
+
+#[test]
+fn first_last_owned_values() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, String> = TableDefinition::new("x");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in [5u64, 1, 9, 3] {
+            table.insert(i, format!("value{i}")).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let decode =
+        |(k, v): (redb::AccessGuard<u64>, redb::AccessGuard<String>)| (k.value(), v.value());
+    let first: Option<(u64, String)> = table.first().unwrap().map(decode);
+    let last: Option<(u64, String)> = table.last().unwrap().map(decode);
+    assert_eq!(first, Some((1, "value1".to_string())));
+    assert_eq!(last, Some((9, "value9".to_string())));
+}
+
+#[test]
+fn first_last_per_table() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definitions: Vec<TableDefinition<u64, u64>> = vec![
+        TableDefinition::new("t0"),
+        TableDefinition::new("t1"),
+        TableDefinition::new("t2"),
+    ];
+    let write_txn = db.begin_write().unwrap();
+    for (t, definition) in definitions.iter().enumerate() {
+        let mut table = write_txn.open_table(*definition).unwrap();
+        // Interleave the key ranges, so that every table overlaps its neighbours
+        for i in 0..1000u64 {
+            table.insert(&(i * 3 + t as u64), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    for (t, definition) in definitions.iter().enumerate() {
+        let table = read_txn.open_table(*definition).unwrap();
+        let (key, value) = table.first().unwrap().unwrap();
+        assert_eq!(key.value(), t as u64);
+        assert_eq!(value.value(), 0);
+        let (key, value) = table.last().unwrap().unwrap();
+        assert_eq!(key.value(), 999 * 3 + t as u64);
+        assert_eq!(value.value(), 999);
+    }
+}
+
+#[test]
+fn export_import() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        for i in 0..1000 {
+            table
+                .insert(format!("key{i}").as_str(), "x".repeat(i % 50).as_str())
+                .unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let mut exported = vec![];
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    table.export(&mut exported).unwrap();
+
+    let tmpfile2 = create_tempfile();
+    let db2 = Database::create(tmpfile2.path()).unwrap();
+    let write_txn = db2.begin_write().unwrap();
+    {
+        let mut table2 = write_txn.open_table(STR_TABLE).unwrap();
+        table2.import(exported.as_slice()).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn2 = db2.begin_read().unwrap();
+    let table2 = read_txn2.open_table(STR_TABLE).unwrap();
+    assert_eq!(table2.len().unwrap(), 1000);
+    for (entry, entry2) in table.iter().unwrap().zip(table2.iter().unwrap()) {
+        let (key, value) = entry.unwrap();
+        let (key2, value2) = entry2.unwrap();
+        assert_eq!(key.value(), key2.value());
+        assert_eq!(value.value(), value2.value());
+    }
+
+    let write_txn = db2.begin_write().unwrap();
+    {
+        let mut table2 = write_txn.open_table(STR_TABLE).unwrap();
+        table2.drain::<&str>(..).unwrap();
+        // Truncated input
+        assert!(matches!(
+            table2.import(&exported[..exported.len() - 1]),
+            Err(StorageError::Corrupted(_))
+        ));
+        assert!(table2.is_empty().unwrap());
+        // Invalid UTF-8
+        let mut invalid = 1u64.to_le_bytes().to_vec();
+        for bytes in [&[0xffu8][..], b"value"] {
+            invalid.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            invalid.extend_from_slice(bytes);
+        }
+        assert!(matches!(
+            table2.import(invalid.as_slice()),
+            Err(StorageError::Corrupted(_))
+        ));
+        // A length prefix far longer than the input, which must not be allocated up front
+        let mut huge = 1u64.to_le_bytes().to_vec();
+        huge.extend_from_slice(&(1u32 << 31).to_le_bytes());
+        huge.extend_from_slice(b"key");
+        assert!(matches!(
+            table2.import(huge.as_slice()),
+            Err(StorageError::Corrupted(_))
+        ));
+        assert!(table2.is_empty().unwrap());
+    }
+    {
+        let mut table2 = write_txn.open_table(U64_TABLE).unwrap();
+        // Keys out of order
+        let mut unsorted = 2u64.to_le_bytes().to_vec();
+        for key in [2u64, 1] {
+            for bytes in [key.to_le_bytes(), 0u64.to_le_bytes()] {
+                unsorted.extend_from_slice(&8u32.to_le_bytes());
+                unsorted.extend_from_slice(&bytes);
+            }
+        }
+        assert!(matches!(
+            table2.import(unsorted.as_slice()),
+            Err(StorageError::Corrupted(_))
+        ));
+        assert!(table2.is_empty().unwrap());
+        // A key with the wrong width for u64
+        let mut wrong_width = 1u64.to_le_bytes().to_vec();
+        for bytes in [&[1u8, 2, 3, 4][..], &0u64.to_le_bytes()] {
+            wrong_width.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            wrong_width.extend_from_slice(bytes);
+        }
+        assert!(matches!(
+            table2.import(wrong_width.as_slice()),
+            Err(StorageError::Corrupted(_))
+        ));
+        assert!(table2.is_empty().unwrap());
+
+        // Keys before, between and after those already in the table
+        for i in 0..100u64 {
+            table2.insert(i * 2, 0).unwrap();
+        }
+        let mut merged = 200u64.to_le_bytes().to_vec();
+        for key in (0..200u64).map(|i| i * 2 + 1) {
+            for bytes in [key.to_le_bytes(), (key * 10).to_le_bytes()] {
+                merged.extend_from_slice(&8u32.to_le_bytes());
+                merged.extend_from_slice(&bytes);
+            }
+        }
+        // A failure after many entries have been read leaves the table as it was
+        assert!(matches!(
+            table2.import(&merged[..merged.len() - 1]),
+            Err(StorageError::Corrupted(_))
+        ));
+        assert_eq!(table2.len().unwrap(), 100);
+        table2.import(merged.as_slice()).unwrap();
+        assert_eq!(table2.len().unwrap(), 300);
+        for key in 0..400u64 {
+            let expected = match key % 2 {
+                1 => Some(key * 10),
+                _ if key < 200 => Some(0),
+                _ => None,
+            };
+            assert_eq!(table2.get(key).unwrap().map(|v| v.value()), expected);
+        }
+        table2.verify_structure().unwrap();
+    }
+    write_txn.abort().unwrap();
+}
+
+#[test]
+fn peek_range() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10u64 {
+            table.insert(&i, &(i * 2)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let mut iter = table.range(3..7).unwrap().peekable();
+    assert_eq!(iter.peek().unwrap().as_ref().unwrap().0.value(), 3);
+    assert_eq!(iter.peek().unwrap().as_ref().unwrap().0.value(), 3);
+    let (key, value) = iter.next().unwrap().unwrap();
+    assert_eq!(key.value(), 3);
+    assert_eq!(value.value(), 6);
+    assert_eq!(iter.peek().unwrap().as_ref().unwrap().0.value(), 4);
+
+    let mut iter = table.range(3..7).unwrap().rev().peekable();
+    assert_eq!(iter.peek().unwrap().as_ref().unwrap().0.value(), 6);
+    assert_eq!(iter.next().unwrap().unwrap().0.value(), 6);
+    for _ in 0..3 {
+        iter.next().unwrap().unwrap();
+    }
+    assert!(iter.peek().is_none());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn pop_drains_in_order() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        // Insert in a scrambled order
+        for i in 0..1000u64 {
+            let key = (i * 7919) % 1000;
+            table.insert(&key, &(key * 2)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..500 {
+            let (key, value) = table.pop_first().unwrap().unwrap();
+            assert_eq!(key.value(), i);
+            assert_eq!(value.value(), i * 2);
+        }
+        for i in (500..1000).rev() {
+            let (key, value) = table.pop_last().unwrap().unwrap();
+            assert_eq!(key.value(), i);
+            assert_eq!(value.value(), i * 2);
+        }
+        assert!(table.pop_first().unwrap().is_none());
+        assert_eq!(table.stats().unwrap().tree_height(), 0);
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert!(table.is_empty().unwrap());
+}
+
+#[test]
+fn get_or_insert_with() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+
+        let mut calls = 0;
+        let value = table
+            .get_or_insert_with("hello", || {
+                calls += 1;
+                "replaced"
+            })
+            .unwrap();
+        assert_eq!(value.value(), "world");
+        drop(value);
+        assert_eq!(calls, 0);
+
+        let value = table
+            .get_or_insert_with("hello2", || {
+                calls += 1;
+                "world2"
+            })
+            .unwrap();
+        assert_eq!(value.value(), "world2");
+        drop(value);
+        assert_eq!(calls, 1);
+        assert_eq!(table.len().unwrap(), 2);
+
+        // Starting from an empty table, and splitting pages as the table grows
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000u64 {
+            let key = (i * 7) % 1000;
+            let value = table.get_or_insert_with(&key, || key * 2).unwrap();
+            assert_eq!(value.value(), key * 2);
+        }
+        for i in 0..1000u64 {
+            let value = table
+                .get_or_insert_with(&i, || -> u64 { unreachable!() })
+                .unwrap();
+            assert_eq!(value.value(), i * 2);
+        }
+        assert_eq!(table.len().unwrap(), 1000);
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+    assert_eq!(table.get("hello2").unwrap().unwrap().value(), "world2");
+}
+
+#[test]
+fn insert_if_absent() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert!(table.insert_if_absent(&0, &0).unwrap());
+        assert!(!table.insert_if_absent(&0, &1).unwrap());
+        assert_eq!(table.get(&0).unwrap().unwrap().value(), 0);
+        for i in 1..1000 {
+            assert!(table.insert_if_absent(&(2 * i), &i).unwrap());
+        }
+    }
+    write_txn.commit().unwrap();
+
+    // A key which is already present leaves the committed pages as they are, rather than copying
+    // them
+    let write_txn = db.begin_write().unwrap();
+    let allocated = write_txn.stats().unwrap().allocated_pages();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            assert!(!table.insert_if_absent(&(2 * i), &0).unwrap());
+        }
+    }
+    assert_eq!(write_txn.stats().unwrap().allocated_pages(), allocated);
+    write_txn.abort().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        // Every existing key is tried, so this includes the keys that separate the branch pages
+        for i in 0..1000 {
+            assert!(!table.insert_if_absent(&(2 * i), &0).unwrap());
+            assert!(table.insert_if_absent(&(2 * i + 1), &0).unwrap());
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 2000);
+    for i in 0..1000 {
+        assert_eq!(table.get(&(2 * i)).unwrap().unwrap().value(), i);
+        assert_eq!(table.get(&(2 * i + 1)).unwrap().unwrap().value(), 0);
+    }
 }
 
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple8_type() {
+fn insert_many() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8, i16), (u16, u32)> =
-        TableDefinition::new("table");
-
+    let batch: TableDefinition<u64, &str> = TableDefinition::new("batch");
+    let single: TableDefinition<u64, &str> = TableDefinition::new("single");
+    let values: Vec<String> = (0..1000u64).map(|i| format!("value_{i}")).collect();
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
+        let mut table = write_txn.open_table(batch).unwrap();
         table
-            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2), &(0, 123))
+            .insert_many((0..1000u64).map(|i| (i, values[i as usize].as_str())))
+            .unwrap();
+        // Overwrite some of the existing keys
+        table
+            .insert_many((0..1000u64).step_by(3).map(|i| (i, "replaced")))
             .unwrap();
     }
+    {
+        let mut table = write_txn.open_table(single).unwrap();
+        for i in 0..1000u64 {
+            table.insert(i, values[i as usize].as_str()).unwrap();
+        }
+        for i in (0..1000u64).step_by(3) {
+            table.insert(i, "replaced").unwrap();
+        }
+    }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(
-        table
-            .get(&("hello", 5, 6, 7, 8, 9, -1, -2))
-            .unwrap()
-            .unwrap()
-            .value(),
-        (0, 123)
-    );
+    let batch_table = read_txn.open_table(batch).unwrap();
+    let single_table = read_txn.open_table(single).unwrap();
+    assert_eq!(batch_table.len().unwrap(), 1000);
+    for (x, y) in batch_table
+        .iter()
+        .unwrap()
+        .zip(single_table.iter().unwrap())
+    {
+        let (batch_key, batch_value) = x.unwrap();
+        let (single_key, single_value) = y.unwrap();
+        assert_eq!(batch_key.value(), single_key.value());
+        assert_eq!(batch_value.value(), single_value.value());
+    }
 }
 
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple9_type() {
+fn extend_sorted_append() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8, i16, i32), (u16, u32)> =
-        TableDefinition::new("table");
+    let sorted: TableDefinition<u64, &str> = TableDefinition::new("sorted");
+    let single: TableDefinition<u64, &str> = TableDefinition::new("single");
+    let values: Vec<String> = (0..50_000u64).map(|i| format!("value_{i}")).collect();
+    let write_txn = db.begin_write().unwrap();
+    for definition in [sorted, single] {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..1000u64 {
+            table.insert(i, values[i as usize].as_str()).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
+        let mut table = write_txn.open_table(sorted).unwrap();
         table
-            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2, -3), &(0, 123))
+            .extend_sorted((1000..50_000u64).map(|i| (i, values[i as usize].as_str())))
             .unwrap();
+        table.verify_structure().unwrap();
+        let mut table = write_txn.open_table(single).unwrap();
+        for i in 1000..50_000u64 {
+            table.insert(i, values[i as usize].as_str()).unwrap();
+        }
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(
-        table
-            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3))
-            .unwrap()
-            .unwrap()
-            .value(),
-        (0, 123)
-    );
+    let sorted_table = read_txn.open_table(sorted).unwrap();
+    let single_table = read_txn.open_table(single).unwrap();
+    assert_eq!(sorted_table.len().unwrap(), 50_000);
+    for (x, y) in sorted_table
+        .iter()
+        .unwrap()
+        .zip(single_table.iter().unwrap())
+    {
+        let (sorted_key, sorted_value) = x.unwrap();
+        let (single_key, single_value) = y.unwrap();
+        assert_eq!(sorted_key.value(), single_key.value());
+        assert_eq!(sorted_value.value(), single_value.value());
+    }
+    sorted_table.verify_structure().unwrap();
+    // Appended pages are filled, rather than split in half
+    let sorted_pages: u64 = sorted_table.stats().unwrap().pages_per_level().iter().sum();
+    let single_pages: u64 = single_table.stats().unwrap().pages_per_level().iter().sum();
+    assert!(sorted_pages < single_pages);
 }
 
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple10_type() {
+fn extend_sorted_empty_table() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<(&str, u8, u16, u32, u64, u128, i8, i16, i32, i64), (u16, u32)> =
-        TableDefinition::new("table");
-
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.extend_sorted(Vec::<(u64, u64)>::new()).unwrap();
+        assert!(table.is_empty().unwrap());
         table
-            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4), &(0, 123))
+            .extend_sorted((0..100_000u64).map(|i| (i, i * 2)))
             .unwrap();
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(
-        table
-            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4))
-            .unwrap()
-            .unwrap()
-            .value(),
-        (0, 123)
-    );
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 100_000);
+    assert!(table.stats().unwrap().tree_height() > 2);
+    table.verify_structure().unwrap();
+    for (i, entry) in table.iter().unwrap().enumerate() {
+        let (key, value) = entry.unwrap();
+        assert_eq!(key.value(), i as u64);
+        assert_eq!(value.value(), 2 * i as u64);
+    }
 }
 
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple11_type() {
+fn extend_sorted_interleaved() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
-    let table_def: TableDefinition<
-        (&str, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128),
-        (u16, u32),
-    > = TableDefinition::new("table");
-
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in (0..2000u64).step_by(2) {
+            table.insert(i, 0).unwrap();
+        }
+        // Keys below 1998 interleave with the existing ones, and the rest are appended
         table
-            .insert(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5), &(0, 123))
+            .extend_sorted((1000..3000u64).map(|i| (i, 1)))
             .unwrap();
+        table.verify_structure().unwrap();
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
-    assert_eq!(
-        table
-            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5))
-            .unwrap()
-            .unwrap()
-            .value(),
-        (0, 123)
-    );
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let mut expected: Vec<(u64, u64)> = (0..1000u64).step_by(2).map(|i| (i, 0)).collect();
+    expected.extend((1000..3000u64).map(|i| (i, 1)));
+    let actual: Vec<(u64, u64)> = table
+        .iter()
+        .unwrap()
+        .map(|x| {
+            let (key, value) = x.unwrap();
+            (key.value(), value.value())
+        })
+        .collect();
+    assert_eq!(actual, expected);
 }
 
 #[test]
-#[allow(clippy::type_complexity)]
-fn tuple12_type() {
+#[should_panic(expected = "extend_sorted() requires keys in strictly increasing order")]
+fn extend_sorted_unsorted() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    let mut table = write_txn.open_table(U64_TABLE).unwrap();
+    table.extend_sorted([(1u64, 0u64), (3, 0), (2, 0)]).unwrap();
+}
 
-    let table_def: TableDefinition<
-        (&str, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, &str),
-        (u16, u32),
-    > = TableDefinition::new("table");
-
+#[test]
+fn insert_reserve() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let def: TableDefinition<&str, &[u8]> = TableDefinition::new("x");
+    let value = "world";
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(table_def).unwrap();
-        table
-            .insert(
-                &("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5, "end"),
-                &(0, 123),
-            )
+        let mut table = write_txn.open_table(def).unwrap();
+        let mut reserved = table
+            .insert_reserve("hello", value.len().try_into().unwrap())
             .unwrap();
+        reserved.as_mut().copy_from_slice(value.as_bytes());
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(table_def).unwrap();
+    let table = read_txn.open_table(def).unwrap();
     assert_eq!(
-        table
-            .get(&("hello", 5, 6, 7, 8, 9, -1, -2, -3, -4, -5, "end"))
-            .unwrap()
-            .unwrap()
-            .value(),
-        (0, 123)
+        value.as_bytes(),
+        table.get("hello").unwrap().unwrap().value()
     );
 }
 
 #[test]
-fn is_empty() {
+fn range_mut() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
+    let def: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(def).unwrap();
+        for i in 0..1000u64 {
+            table.insert(i, i.to_le_bytes().as_slice()).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(STR_TABLE).unwrap();
-        assert!(table.is_empty().unwrap());
-        table.insert("hello", "world").unwrap();
-        assert!(!table.is_empty().unwrap());
+        let mut table = write_txn.open_table(def).unwrap();
+        let mut iter = table.range_mut(100..900).unwrap();
+        let mut expected_key = 100;
+        while let Some(entry) = iter.next() {
+            let (key, mut value) = entry.unwrap();
+            assert_eq!(key.value(), expected_key);
+            let counter = u64::from_le_bytes(value.as_mut().try_into().unwrap());
+            value.as_mut().copy_from_slice(&(counter + 1).to_le_bytes());
+            expected_key += 1;
+        }
+        assert_eq!(expected_key, 900);
+        assert!(table.range_mut(2000..).unwrap().next().is_none());
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(STR_TABLE).unwrap();
-    assert!(!table.is_empty().unwrap());
+    let table = read_txn.open_table(def).unwrap();
+    for entry in table.iter().unwrap() {
+        let (key, value) = entry.unwrap();
+        let key = key.value();
+        let expected = if (100..900).contains(&key) {
+            key + 1
+        } else {
+            key
+        };
+        assert_eq!(value.value(), expected.to_le_bytes());
+    }
 }
 
 #[test]
-fn abort() {
+fn get_mut_partial_write() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-
+    let def: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let value: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(STR_TABLE).unwrap();
-        table.insert("hello", "aborted").unwrap();
-        assert_eq!("aborted", table.get("hello").unwrap().unwrap().value());
+        let mut table = write_txn.open_table(def).unwrap();
+        table.insert(0, value.as_slice()).unwrap();
     }
-    write_txn.abort().unwrap();
-
-    let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(STR_TABLE);
-    assert!(table.is_err());
+    write_txn.commit().unwrap();
 
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(STR_TABLE).unwrap();
-        table.insert("hello", "world").unwrap();
+        let mut table = write_txn.open_table(def).unwrap();
+        let mut guard = table.get_mut(0).unwrap().unwrap();
+        assert_eq!(guard.as_mut_range(100..116).len(), 16);
+        guard.as_mut_range(100..116).fill(0xFF);
+        assert!(guard.as_mut_range(10_000..10_000).is_empty());
     }
     write_txn.commit().unwrap();
 
+    let mut expected = value;
+    expected[100..116].fill(0xFF);
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(STR_TABLE).unwrap();
-    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
-    assert_eq!(table.len().unwrap(), 1);
+    let table = read_txn.open_table(def).unwrap();
+    assert_eq!(expected, table.get(0).unwrap().unwrap().value());
 }
 
 #[test]
-fn insert_overwrite() {
+#[should_panic(expected = "is out of bounds for a value of length 10")]
+fn get_mut_partial_write_out_of_bounds() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
+    let def: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let write_txn = db.begin_write().unwrap();
+    let mut table = write_txn.open_table(def).unwrap();
+    table.insert(0, [0u8; 10].as_slice()).unwrap();
+    let mut guard = table.get_mut(0).unwrap().unwrap();
+    guard.as_mut_range(5..11);
+}
+
+#[test]
+fn get_mut() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let def: TableDefinition<&str, &[u8]> = TableDefinition::new("x");
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(STR_TABLE).unwrap();
-        assert!(table.insert("hello", "world").unwrap().is_none());
+        let mut table = write_txn.open_table(def).unwrap();
+        table.insert("hello", b"world".as_slice()).unwrap();
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(STR_TABLE).unwrap();
-    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
-
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(STR_TABLE).unwrap();
-        let old_value = table.insert("hello", "replaced").unwrap();
-        assert_eq!(old_value.unwrap().value(), "world");
+        let mut table = write_txn.open_table(def).unwrap();
+        assert!(table.get_mut("missing").unwrap().is_none());
+        let mut guard = table.get_mut("hello").unwrap().unwrap();
+        assert_eq!(guard.as_mut().len(), 5);
+        guard.as_mut().copy_from_slice(b"WORLD");
+        drop(guard);
+        assert_eq!(b"WORLD", table.get("hello").unwrap().unwrap().value());
+    }
+    // The value's page was copied by the first get_mut(), so the second modifies it in place
+    let allocated = write_txn.stats().unwrap().allocated_pages();
+    {
+        let mut table = write_txn.open_table(def).unwrap();
+        table.get_mut("hello").unwrap().unwrap().as_mut()[0] = b'w';
     }
+    assert_eq!(write_txn.stats().unwrap().allocated_pages(), allocated);
     write_txn.commit().unwrap();
 
+    // The committed value must not have been modified in place
+    let table = read_txn.open_table(def).unwrap();
+    assert_eq!(b"world", table.get("hello").unwrap().unwrap().value());
+
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(STR_TABLE).unwrap();
-    assert_eq!("replaced", table.get("hello").unwrap().unwrap().value());
+    let table = read_txn.open_table(def).unwrap();
+    assert_eq!(b"wORLD", table.get("hello").unwrap().unwrap().value());
 }
 
 #[test]
-fn insert_reserve() {
+fn append_value() {
     let tmpfile = create_tempfile();
     let db = Database::create(tmpfile.path()).unwrap();
-    let def: TableDefinition<&str, &[u8]> = TableDefinition::new("x");
-    let value = "world";
     let write_txn = db.begin_write().unwrap();
     {
-        let mut table = write_txn.open_table(def).unwrap();
-        let mut reserved = table
-            .insert_reserve("hello", value.len().try_into().unwrap())
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"a".as_slice(), b"".as_slice()).unwrap();
+        table
+            .insert(b"c".as_slice(), b"neighbor".as_slice())
             .unwrap();
-        reserved.as_mut().copy_from_slice(value.as_bytes());
+        table
+            .append_value(b"b".as_slice(), b"hello".as_slice())
+            .unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let mut expected = b"hello".to_vec();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(SLICE_TABLE).unwrap();
+        // Grow the value until it no longer fits in a single page alongside its neighbors
+        for i in 0..1000u32 {
+            table
+                .append_value(b"b".as_slice(), &i.to_le_bytes())
+                .unwrap();
+            expected.extend_from_slice(&i.to_le_bytes());
+        }
+        assert_eq!(
+            table.get(b"b".as_slice()).unwrap().unwrap().value(),
+            expected
+        );
     }
     write_txn.commit().unwrap();
 
     let read_txn = db.begin_read().unwrap();
-    let table = read_txn.open_table(def).unwrap();
+    let table = read_txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 3);
     assert_eq!(
-        value.as_bytes(),
-        table.get("hello").unwrap().unwrap().value()
+        table.get(b"b".as_slice()).unwrap().unwrap().value(),
+        expected
     );
+    assert_eq!(
+        table.get(b"c".as_slice()).unwrap().unwrap().value(),
+        b"neighbor"
+    );
+    assert!(table
+        .get(b"a".as_slice())
+        .unwrap()
+        .unwrap()
+        .value()
+        .is_empty());
 }
 
 #[test]
@@ -856,10 +4022,56 @@ fn read_isolation2() {
     assert_eq!("world3", table2.get("hello3").unwrap().unwrap().value());
     assert_eq!(table2.len().unwrap(), 2);
 
-    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
-    assert!(table.get("hello2").unwrap().is_none());
-    assert!(table.get("hello3").unwrap().is_none());
-    assert_eq!(table.len().unwrap(), 1);
+    assert_eq!("world", table.get("hello").unwrap().unwrap().value());
+    assert!(table.get("hello2").unwrap().is_none());
+    assert!(table.get("hello3").unwrap().is_none());
+    assert_eq!(table.len().unwrap(), 1);
+}
+
+#[test]
+fn read_isolation_multi_level() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000u64 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+
+    // Rewrite every page of the tree across several commits. Pages reachable from the read
+    // transaction must not be freed and reused until it is dropped
+    for round in 1..4u64 {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut write_table = write_txn.open_table(U64_TABLE).unwrap();
+            write_table.remove_range(1_000..9_000).unwrap();
+            for i in 0..10_000u64 {
+                write_table.insert(&i, &(i * round + 1)).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+    }
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut write_table = write_txn.open_table(U64_TABLE).unwrap();
+        write_table.clear().unwrap();
+        write_table.insert(&0, &0).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    assert_eq!(table.len().unwrap(), 10_000);
+    for (i, entry) in table.iter().unwrap().enumerate() {
+        let (key, value) = entry.unwrap();
+        assert_eq!(key.value(), i as u64);
+        assert_eq!(value.value(), i as u64);
+    }
+    table.verify_structure().unwrap();
 }
 
 #[test]
@@ -929,6 +4141,45 @@ fn i128_type() {
     assert!(iter.next().is_none());
 }
 
+#[test]
+fn little_endian_integer_keys() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<i64, ()> = TableDefinition::new("x");
+    let keys = [i64::MAX, 256, -1, 255, 0, i64::MIN, -256, 1, -255];
+
+    assert_eq!(<i64>::as_bytes(&256), 256i64.to_le_bytes());
+    // Byte-wise order differs from numeric order, for both signs
+    assert!(<i64>::as_bytes(&256) < <i64>::as_bytes(&255));
+    assert!(<i64>::as_bytes(&-1) > <i64>::as_bytes(&-256));
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for key in keys {
+            table.insert(key, ()).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let mut expected = keys.to_vec();
+    expected.sort();
+    let stored: Vec<i64> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value())
+        .collect();
+    assert_eq!(stored, expected);
+    let negative: Vec<i64> = table
+        .range(-256..0)
+        .unwrap()
+        .map(|x| x.unwrap().0.value())
+        .collect();
+    assert_eq!(negative, vec![-256, -255, -1]);
+}
+
 #[test]
 fn f32_type() {
     let tmpfile = create_tempfile();
@@ -975,6 +4226,82 @@ fn str_type() {
     assert!(iter.next().is_none());
 }
 
+#[test]
+fn string_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let definition: TableDefinition<String, String> = TableDefinition::new("x");
+    let u64_definition: TableDefinition<String, u64> = TableDefinition::new("y");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        table
+            .insert("hello".to_string(), "world".to_string())
+            .unwrap();
+        table.insert("a".to_string(), String::new()).unwrap();
+        let mut table = write_txn.open_table(u64_definition).unwrap();
+        table.insert("hello".to_string(), 5).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let value: String = table.get("hello".to_string()).unwrap().unwrap().value();
+    assert_eq!(value, "world");
+    assert_eq!(table.get("a".to_string()).unwrap().unwrap().value(), "");
+    assert!(table.get("b".to_string()).unwrap().is_none());
+    let keys: Vec<String> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value())
+        .collect();
+    assert_eq!(keys, vec!["a", "hello"]);
+    let table = read_txn.open_table(u64_definition).unwrap();
+    assert_eq!(table.get("hello".to_string()).unwrap().unwrap().value(), 5);
+
+    // String and &str are distinct types
+    assert!(read_txn
+        .open_table::<&str, &str>(TableDefinition::new("x"))
+        .is_err());
+}
+
+#[test]
+fn vec_type() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+
+    let definition: TableDefinition<u64, Vec<u32>> = TableDefinition::new("x");
+    let str_definition: TableDefinition<u64, Vec<&str>> = TableDefinition::new("y");
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for len in [0u64, 1, 2, 100, 5000] {
+            let value: Vec<u32> = (0..len as u32).map(|i| i * 3).collect();
+            table.insert(len, &value).unwrap();
+        }
+        let mut table = write_txn.open_table(str_definition).unwrap();
+        table.insert(0, &vec![]).unwrap();
+        table.insert(1, &vec!["hello", "", "world"]).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    for len in [0u64, 1, 2, 100, 5000] {
+        let expected: Vec<u32> = (0..len as u32).map(|i| i * 3).collect();
+        assert_eq!(table.get(len).unwrap().unwrap().value(), expected);
+    }
+    let table = read_txn.open_table(str_definition).unwrap();
+    assert!(table.get(0).unwrap().unwrap().value().is_empty());
+    assert_eq!(
+        table.get(1).unwrap().unwrap().value(),
+        vec!["hello", "", "world"]
+    );
+}
+
 #[test]
 fn empty_type() {
     let tmpfile = create_tempfile();
@@ -1089,6 +4416,58 @@ fn drain_lifetime() {
     assert!(iter.next().is_none());
 }
 
+#[test]
+fn drain_filter_even_keys() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(&i, &(i * 3)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let removed: Vec<(u64, u64)> = table
+            .drain_filter::<u64, _>(.., |k, _| k % 2 == 0)
+            .unwrap()
+            .map(|x| {
+                let (k, v) = x.unwrap();
+                (k.value(), v.value())
+            })
+            .collect();
+        let expected: Vec<(u64, u64)> = (0..1000).step_by(2).map(|i| (i, i * 3)).collect();
+        assert_eq!(removed, expected);
+        let survivors: Vec<u64> = table
+            .iter()
+            .unwrap()
+            .map(|x| x.unwrap().0.value())
+            .collect();
+        assert_eq!(survivors, (1..1000).step_by(2).collect::<Vec<u64>>());
+
+        // Remove the remaining entries, which empties the tree
+        assert_eq!(
+            table
+                .drain_filter::<u64, _>(.., |_, _| true)
+                .unwrap()
+                .count(),
+            500
+        );
+        assert!(table.is_empty().unwrap());
+        assert_eq!(table.stats().unwrap().tree_height(), 0);
+        table.insert(&1, &1).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 1);
+}
+
 #[test]
 fn drain_filter_lifetime() {
     let tmpfile = create_tempfile();
@@ -1184,6 +4563,320 @@ fn custom_ordering() {
     assert!(iter.next().is_none());
 }
 
+#[test]
+fn case_insensitive_ordering() {
+    #[derive(Debug)]
+    struct CaseInsensitive(String);
+
+    impl RedbValue for CaseInsensitive {
+        type SelfType<'a> = CaseInsensitive
+        where
+        Self: 'a;
+        type AsBytes<'a> = &'a [u8]
+        where
+        Self: 'a;
+
+        fn fixed_width() -> Option<usize> {
+            None
+        }
+
+        fn from_bytes<'a>(data: &'a [u8]) -> CaseInsensitive
+        where
+            Self: 'a,
+        {
+            CaseInsensitive(std::str::from_utf8(data).unwrap().to_string())
+        }
+
+        fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a [u8]
+        where
+            Self: 'a,
+            Self: 'b,
+        {
+            value.0.as_bytes()
+        }
+
+        fn type_name() -> TypeName {
+            TypeName::new("test::CaseInsensitive")
+        }
+    }
+
+    impl RedbKey for CaseInsensitive {
+        fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+            let lower1 = data1.iter().map(u8::to_ascii_lowercase);
+            let lower2 = data2.iter().map(u8::to_ascii_lowercase);
+            lower1.cmp(lower2)
+        }
+    }
+
+    let definition: TableDefinition<CaseInsensitive, u64> = TableDefinition::new("x");
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for (i, word) in ["banana", "Apple", "cherry", "apricot"].iter().enumerate() {
+            table
+                .insert(&CaseInsensitive(word.to_string()), &(i as u64))
+                .unwrap();
+        }
+        // Keys which differ only in case are the same key
+        let old = table
+            .insert(&CaseInsensitive("BANANA".to_string()), &10)
+            .unwrap();
+        assert_eq!(old.unwrap().value(), 0);
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    assert_eq!(table.len().unwrap(), 4);
+    let key = CaseInsensitive("CHERRY".to_string());
+    assert_eq!(table.get(&key).unwrap().unwrap().value(), 2);
+    let keys: Vec<String> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value().0)
+        .collect();
+    // The stored spelling is the one from the first insert
+    assert_eq!(keys, vec!["Apple", "apricot", "banana", "cherry"]);
+}
+
+#[test]
+fn verify_structure() {
+    // Both orderings share a type name, so that a table written with one ordering can be read
+    // with the other, which is equivalent to its keys being corrupted
+    #[derive(Debug)]
+    struct OrderedKey<const REVERSE: bool>(Vec<u8>);
+
+    impl<const REVERSE: bool> RedbValue for OrderedKey<REVERSE> {
+        type SelfType<'a> = OrderedKey<REVERSE>
+        where
+        Self: 'a;
+        type AsBytes<'a> = &'a [u8]
+        where
+        Self: 'a;
+
+        fn fixed_width() -> Option<usize> {
+            None
+        }
+
+        fn from_bytes<'a>(data: &'a [u8]) -> OrderedKey<REVERSE>
+        where
+            Self: 'a,
+        {
+            OrderedKey(data.to_vec())
+        }
+
+        fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a [u8]
+        where
+            Self: 'a,
+            Self: 'b,
+        {
+            &value.0
+        }
+
+        fn type_name() -> TypeName {
+            TypeName::new("test::OrderedKey")
+        }
+    }
+
+    impl<const REVERSE: bool> RedbKey for OrderedKey<REVERSE> {
+        fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+            if REVERSE {
+                data2.cmp(data1)
+            } else {
+                data1.cmp(data2)
+            }
+        }
+    }
+
+    let forward: TableDefinition<OrderedKey<false>, u64> = TableDefinition::new("x");
+    let reverse: TableDefinition<OrderedKey<true>, u64> = TableDefinition::new("x");
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(forward).unwrap();
+        table.verify_structure().unwrap();
+        for i in 0..1000u64 {
+            table
+                .insert(&OrderedKey(i.to_be_bytes().to_vec()), &i)
+                .unwrap();
+        }
+        assert!(table.stats().unwrap().tree_height() > 1);
+        table.verify_structure().unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    read_txn
+        .open_table(forward)
+        .unwrap()
+        .verify_structure()
+        .unwrap();
+    let table = read_txn.open_table(reverse).unwrap();
+    match table.verify_structure() {
+        Err(StorageError::Corrupted(msg)) => assert!(msg.contains("page"), "{msg}"),
+        other => panic!("Expected corruption, got {other:?}"),
+    }
+}
+
+// Key type for testing inconsistent RedbKey::compare implementations. All modes share a type
+// name, so that a table written with a correct ordering can be read with a broken one
+#[cfg(debug_assertions)]
+#[derive(Debug)]
+struct BrokenKey<const MODE: u8>(u64);
+
+#[cfg(debug_assertions)]
+impl<const MODE: u8> BrokenKey<MODE> {
+    const FORWARD: u8 = 0;
+    // Treats distinct keys as equal, which is inconsistent with their serialized form
+    const COARSE: u8 = 1;
+    const ALWAYS_LESS: u8 = 2;
+}
+
+#[cfg(debug_assertions)]
+impl<const MODE: u8> RedbValue for BrokenKey<MODE> {
+    type SelfType<'a> = BrokenKey<MODE>
+    where
+        Self: 'a;
+    type AsBytes<'a> = [u8; 8]
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        Some(8)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> BrokenKey<MODE>
+    where
+        Self: 'a,
+    {
+        BrokenKey(u64::from_le_bytes(data.try_into().unwrap()))
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> [u8; 8]
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.0.to_le_bytes()
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::new("test::BrokenKey")
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<const MODE: u8> RedbKey for BrokenKey<MODE> {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        let value1 = u64::from_le_bytes(data1.try_into().unwrap());
+        let value2 = u64::from_le_bytes(data2.try_into().unwrap());
+        match MODE {
+            Self::FORWARD => value1.cmp(&value2),
+            Self::COARSE => (value1 / 10).cmp(&(value2 / 10)),
+            Self::ALWAYS_LESS => Ordering::Less,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "test::BrokenKey::compare is not antisymmetric")]
+fn broken_compare_antisymmetry() {
+    let forward: TableDefinition<BrokenKey<{ BrokenKey::<0>::FORWARD }>, u64> =
+        TableDefinition::new("x");
+    let always_less: TableDefinition<BrokenKey<{ BrokenKey::<0>::ALWAYS_LESS }>, u64> =
+        TableDefinition::new("x");
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(forward).unwrap();
+        for i in 0..10u64 {
+            table.insert(&BrokenKey(i), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(always_less).unwrap();
+    table.get(&BrokenKey(5)).unwrap();
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "test::BrokenKey::compare is not a total order")]
+fn broken_compare_total_order() {
+    let forward: TableDefinition<BrokenKey<{ BrokenKey::<0>::FORWARD }>, u64> =
+        TableDefinition::new("x");
+    let coarse: TableDefinition<BrokenKey<{ BrokenKey::<0>::COARSE }>, u64> =
+        TableDefinition::new("x");
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(forward).unwrap();
+        for i in 0..1000u64 {
+            table.insert(&BrokenKey(i), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    // The stored keys compare equal to their neighbors under the coarse comparison
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(coarse).unwrap();
+    table.get(&BrokenKey(505)).unwrap();
+}
+
+#[test]
+fn reverse_ordering() {
+    let definition: TableDefinition<Reverse<u64>, &str> = TableDefinition::new("x");
+
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in [3u64, 0, 9, 1, 7, 2, 8, 4, 6, 5] {
+            table.insert(&Reverse(i), "value").unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let keys: Vec<u64> = table
+        .iter()
+        .unwrap()
+        .map(|x| x.unwrap().0.value().0)
+        .collect();
+    assert_eq!(keys, (0..10).rev().collect::<Vec<u64>>());
+
+    // Reverse(8) < Reverse(3)
+    let keys: Vec<u64> = table
+        .range(Reverse(8)..Reverse(3))
+        .unwrap()
+        .map(|x| x.unwrap().0.value().0)
+        .collect();
+    assert_eq!(keys, vec![8, 7, 6, 5, 4]);
+    let keys: Vec<u64> = table
+        .range(..=Reverse(7))
+        .unwrap()
+        .rev()
+        .map(|x| x.unwrap().0.value().0)
+        .collect();
+    assert_eq!(keys, vec![7, 8, 9]);
+    assert_eq!(table.get(Reverse(5)).unwrap().unwrap().value(), "value");
+    assert!(table.get(Reverse(10)).unwrap().is_none());
+}
+
 #[test]
 fn owned_get_signatures() {
     let tmpfile = create_tempfile();
@@ -6,7 +6,10 @@ use rand::Rng;
 use redb::{
     Builder, Database, Durability, MultimapTableDefinition, ReadableTable, TableDefinition,
 };
-use redb::{DatabaseError, ReadableMultimapTable, SavepointError, StorageError, TableError};
+use redb::{
+    DatabaseError, ReadableMultimapTable, SavepointError, StorageError, TableError,
+    MAX_VALUE_LENGTH,
+};
 
 const ELEMENTS: usize = 100;
 
@@ -196,6 +199,53 @@ fn free() {
     txn.abort().unwrap();
 }
 
+#[cfg(feature = "serde")]
+#[test]
+fn tree_structure_json() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let definition: TableDefinition<u64, u64> = TableDefinition::new("x");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        assert!(table.tree_structure().unwrap().is_none());
+        table.insert(&2, &20).unwrap();
+        table.insert(&1, &10).unwrap();
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let json = serde_json::to_value(table.tree_structure().unwrap().unwrap()).unwrap();
+    assert_eq!(json["type"], "leaf");
+    assert_eq!(json["page"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        json["entries"],
+        serde_json::json!([["1", "10"], ["2", "20"]])
+    );
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(definition).unwrap();
+        for i in 0..10_000u64 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(definition).unwrap();
+    let json = serde_json::to_value(table.tree_structure().unwrap().unwrap()).unwrap();
+    assert_eq!(json["type"], "branch");
+    let children = json["children"].as_array().unwrap();
+    assert_eq!(json["keys"].as_array().unwrap().len() + 1, children.len());
+    let mut first_leaf = &children[0];
+    while first_leaf["type"] == "branch" {
+        first_leaf = &first_leaf["children"][0];
+    }
+    assert_eq!(first_leaf["entries"][0], serde_json::json!(["0", "0"]));
+}
+
 #[test]
 fn large_values() {
     let tmpfile = create_tempfile();
@@ -225,6 +275,224 @@ fn large_values() {
     txn.commit().unwrap();
 }
 
+#[test]
+fn large_value_round_trip() {
+    let tmpfile = create_tempfile();
+
+    let db = Database::create(tmpfile.path()).unwrap();
+    let mut rng = rand::thread_rng();
+    let mut value = vec![0u8; 5_000_000];
+    rng.fill(value.as_mut_slice());
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"a".as_slice(), b"small".as_slice()).unwrap();
+        table.insert(b"b".as_slice(), value.as_slice()).unwrap();
+        table.insert(b"c".as_slice(), b"small".as_slice()).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.get(b"b".as_slice()).unwrap().unwrap().value(), value);
+    assert_eq!(
+        table.get(b"c".as_slice()).unwrap().unwrap().value(),
+        b"small"
+    );
+    drop(table);
+    drop(txn);
+
+    // Overwrite with a larger value
+    let mut value2 = vec![0u8; 7_000_000];
+    rng.fill(value2.as_mut_slice());
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(SLICE_TABLE).unwrap();
+        let old = table.insert(b"b".as_slice(), value2.as_slice()).unwrap();
+        assert_eq!(old.unwrap().value(), value);
+    }
+    txn.commit().unwrap();
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(SLICE_TABLE).unwrap();
+        assert_eq!(table.get(b"b".as_slice()).unwrap().unwrap().value(), value2);
+        assert_eq!(
+            table.remove(b"b".as_slice()).unwrap().unwrap().value(),
+            value2
+        );
+        assert_eq!(table.len().unwrap(), 2);
+    }
+    txn.commit().unwrap();
+}
+
+#[test]
+fn invalid_page_type() {
+    let tmpfile = create_tempfile();
+    let definition: TableDefinition<&[u8], &[u8]> = TableDefinition::new("x");
+    let marker = b"invalid_page_type_marker";
+
+    let db = Database::create(tmpfile.path()).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(definition).unwrap();
+        table.insert(marker.as_slice(), marker.as_slice()).unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+
+    // Overwrite the type byte of the leaf page holding the entry
+    let mut data = fs::read(tmpfile.path()).unwrap();
+    let offset = data
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .unwrap();
+    data[offset - offset % 4096] = 0xFF;
+    fs::write(tmpfile.path(), data).unwrap();
+
+    let db = Database::open(tmpfile.path()).unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(definition).unwrap();
+    match table.get(marker.as_slice()) {
+        Err(StorageError::Corrupted(msg)) => {
+            assert!(msg.contains("invalid btree page type 255"), "{msg}")
+        }
+        other => panic!("Expected corruption, got {:?}", other.map(|x| x.is_some())),
+    }
+    assert!(matches!(table.iter(), Err(StorageError::Corrupted(_))));
+    assert!(matches!(table.len(), Err(StorageError::Corrupted(_))));
+    assert!(matches!(
+        table.verify_structure(),
+        Err(StorageError::Corrupted(_))
+    ));
+}
+
+#[test]
+fn compact_invalid_page_type() {
+    let tmpfile = create_tempfile();
+    let definition: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+    let marker = b"compact_invalid_page_type_marker";
+
+    let db = Database::create(tmpfile.path()).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(definition).unwrap();
+        for i in 0..1000u64 {
+            table.insert(i, [0u8; 64].as_slice()).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    // Insert the marker separately, so that it's only in the rightmost leaf
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(definition).unwrap();
+        table.insert(1000, marker.as_slice()).unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+
+    let mut data = fs::read(tmpfile.path()).unwrap();
+    let offset = data
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .unwrap();
+    data[offset - offset % 4096] = 0xFF;
+    fs::write(tmpfile.path(), data).unwrap();
+
+    // A failed compaction must leave the table as it was, rather than empty
+    let db = Database::open(tmpfile.path()).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(definition).unwrap();
+        assert!(matches!(table.compact(), Err(StorageError::Corrupted(_))));
+        assert_eq!(table.get(0).unwrap().unwrap().value(), [0u8; 64].as_slice());
+        assert!(matches!(table.compact(), Err(StorageError::Corrupted(_))));
+    }
+    txn.abort().unwrap();
+}
+
+fn check_corrupted_leaf(corrupt: impl Fn(&mut [u8])) {
+    let tmpfile = create_tempfile();
+    let definition: TableDefinition<&[u8], &[u8]> = TableDefinition::new("x");
+    let marker = b"corrupted_leaf_marker";
+
+    let db = Database::create(tmpfile.path()).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(definition).unwrap();
+        table.insert(marker.as_slice(), marker.as_slice()).unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+
+    let mut data = fs::read(tmpfile.path()).unwrap();
+    let offset = data
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .unwrap();
+    let page = offset - offset % 4096;
+    corrupt(&mut data[page..(page + 4096)]);
+    fs::write(tmpfile.path(), data).unwrap();
+
+    // The integrity check must detect the invalid offsets, rather than slicing the page with them
+    let mut db = Database::open(tmpfile.path()).unwrap();
+    match db.check_integrity() {
+        Ok(passed) => assert!(!passed),
+        Err(err) => assert!(matches!(err, StorageError::Corrupted(_)), "{err}"),
+    }
+}
+
+#[test]
+fn invalid_leaf_offsets() {
+    // Key end offset beyond the end of the page
+    check_corrupted_leaf(|page| page[4..8].copy_from_slice(&u32::MAX.to_le_bytes()));
+    // Key end offset before the start of the key
+    check_corrupted_leaf(|page| page[4..8].copy_from_slice(&0u32.to_le_bytes()));
+    // Offset table beyond the end of the page
+    check_corrupted_leaf(|page| page[2..4].copy_from_slice(&u16::MAX.to_le_bytes()));
+    // No entries
+    check_corrupted_leaf(|page| page[2..4].copy_from_slice(&0u16.to_le_bytes()));
+}
+
+#[test]
+fn value_too_large() {
+    let tmpfile = create_tempfile();
+
+    let db = Database::create(tmpfile.path()).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(SLICE_TABLE).unwrap();
+        let too_large = MAX_VALUE_LENGTH + 1;
+        match table.insert_reserve(b"hello".as_slice(), too_large as u32) {
+            Err(StorageError::ValueTooLarge(len)) => assert_eq!(len, too_large),
+            other => panic!("Expected ValueTooLarge, got {:?}", other.map(|_| ())),
+        }
+        // The zeroed allocation is never touched, so isn't backed by memory
+        #[cfg(target_pointer_width = "64")]
+        {
+            let value = vec![0u8; too_large];
+            match table.insert(b"hello".as_slice(), value.as_slice()) {
+                Err(StorageError::ValueTooLarge(len)) => assert_eq!(len, too_large),
+                other => panic!("Expected ValueTooLarge, got {:?}", other.map(|_| ())),
+            }
+            match table.insert(value.as_slice(), b"world".as_slice()) {
+                Err(StorageError::ValueTooLarge(len)) => assert_eq!(len, too_large),
+                other => panic!("Expected ValueTooLarge, got {:?}", other.map(|_| ())),
+            }
+        }
+        // The table is still usable
+        table
+            .insert(b"hello".as_slice(), b"world".as_slice())
+            .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 1);
+}
+
 #[test]
 fn large_keys() {
     let tmpfile = create_tempfile();